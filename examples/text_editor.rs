@@ -10,6 +10,13 @@ use std::path::Path;
 use eframe::{egui, Theme};
 use eframe::egui::Key::S;
 use text_editor::text_editor::{TextEditor, TextEditorOptions};
+use text_editor::text_editor::events::EditorEvent;
+use text_editor::text_editor::font::FontSettings;
+use text_editor::text_editor::git_gutter::GitBase;
+use text_editor::text_editor::lines_transform::{LinesTransform, SortOrder};
+use text_editor::text_editor::tail::TailMode;
+use text_editor::text_editor::text_transform::{TextTransform, Transformation};
+use text_editor::text_editor::workspace::{EditorWorkspace, WorkspaceEvent};
 use crate::egui::Rounding;
 
 #[derive(Default, Debug, Clone)]
@@ -28,7 +35,80 @@ fn main() {
 impl eframe::App for MyApp {
     fn update(&mut self, ctx: &egui::Context, f: &mut eframe::Frame) {
         egui::TopBottomPanel::top("top").show(ctx, |ui| {
-            ui.label("top panel");
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Save").clicked() {
+                        if let Err(error) = self.workspace.save_active() {
+                            println!("Can't save file: {}", error);
+                        }
+                    }
+                    if ui.button("Follow (tail)").clicked() {
+                        if let Some(index) = self.workspace.active_index() {
+                            let document = &mut self.workspace.documents_mut()[index];
+                            if let Some(path) = document.path.clone() {
+                                if document.editor().is_following() {
+                                    document.editor_mut().stop_following();
+                                } else if let Err(error) = document.editor_mut().follow(&path) {
+                                    println!("Can't follow {}: {}", path.display(), error);
+                                }
+                            }
+                        }
+                    }
+                });
+                let maybe_text_editor = self.workspace.active_editor_mut();
+                if maybe_text_editor.is_none() {
+                    return;
+                }
+                let text_editor = maybe_text_editor.unwrap();
+                ui.menu_button("Lines", |ui| {
+                    if ui.button("Sort").clicked() {
+                        text_editor.sort_lines(ui, SortOrder::Lexical);
+                    }
+                    if ui.button("Sort (natural)").clicked() {
+                        text_editor.sort_lines(ui, SortOrder::Natural);
+                    }
+                    if ui.button("Sort (case insensitive)").clicked() {
+                        text_editor.sort_lines(ui, SortOrder::CaseInsensitive);
+                    }
+                    if ui.button("Sort by first column").clicked() {
+                        text_editor.sort_lines(ui, SortOrder::Column(0));
+                    }
+                    if ui.button("Reverse").clicked() {
+                        text_editor.reverse_lines(ui);
+                    }
+                    if ui.button("Unique").clicked() {
+                        text_editor.unique_lines(ui);
+                    }
+                    if ui.button("Shuffle").clicked() {
+                        text_editor.shuffle_lines(ui);
+                    }
+                    if ui.button("Trim trailing whitespace").clicked() {
+                        text_editor.trim_trailing_whitespace(ui);
+                    }
+                });
+                ui.menu_button("View", |ui| {
+                    if ui.button("Zoom in").clicked() {
+                        text_editor.zoom_in();
+                    }
+                    if ui.button("Zoom out").clicked() {
+                        text_editor.zoom_out();
+                    }
+                    if ui.button("Reset zoom").clicked() {
+                        text_editor.set_zoom(1.0);
+                    }
+                });
+                ui.menu_button("Text", |ui| {
+                    for (label, transformation) in [("UPPER CASE", Transformation::Uppercase), ("lower case", Transformation::Lowercase),
+                        ("Title Case", Transformation::TitleCase), ("snake_case", Transformation::SnakeCase),
+                        ("camelCase", Transformation::CamelCase), ("kebab-case", Transformation::KebabCase),
+                        ("Base64 encode", Transformation::Base64Encode), ("Base64 decode", Transformation::Base64Decode),
+                        ("URL encode", Transformation::UrlEncode), ("URL decode", Transformation::UrlDecode)] {
+                        if ui.button(label).clicked() {
+                            text_editor.transform_text(ui, transformation);
+                        }
+                    }
+                });
+            });
         });
         egui::SidePanel::left("left").show(ctx, |ui| {
             ui.label("left panel");
@@ -42,25 +122,47 @@ impl eframe::App for MyApp {
         };
         panel = panel.frame(panel_frame);
         panel.show(ctx, |ui| {
-            self.text_editor.ui(ctx, ui);
+            self.workspace.ui(ctx, ui);
         });
+        for event in self.workspace.take_events() {
+            match event {
+                WorkspaceEvent::Reloaded(path) => println!("Reloaded {}", path.display()),
+                WorkspaceEvent::Conflict(path) => println!("{} changed on disk and has unsaved changes", path.display()),
+                WorkspaceEvent::GitBaseError(path, error) => println!("Can't read git version of {}: {}", path.display(), error),
+            }
+        }
+        for document in self.workspace.documents_mut() {
+            for event in document.editor_mut().take_events() {
+                match event {
+                    EditorEvent::Saved => println!("{} saved", document.title),
+                    EditorEvent::DirtyChanged(is_dirty) => println!("{} dirty: {}", document.title, is_dirty),
+                    _ => {}
+                }
+            }
+        }
     }
 }
 
 struct MyApp {
-    text_editor: TextEditor,
+    workspace: EditorWorkspace,
 }
 impl MyApp {
     fn new(creation_context: &eframe::CreationContext<'_>) -> Self {
         let args: Vec<_> = env::args().collect();
         if args.len() < 2 {
-            println!("Please provide file to open as 1st program argument");
-        } else {
-            println!("Opening {}", args[1].as_str());
+            println!("Please provide files to open as program arguments");
+        }
+        let options = TextEditorOptions { rulers: vec![80, 100], minimap: true, sticky_scroll: true, git_base: Some(GitBase::Index), inline_blame: true, emit_events: true, ..TextEditorOptions::default() };
+        let mut workspace = EditorWorkspace::new(creation_context, options);
+        for arg in args.iter().skip(1) {
+            println!("Opening {}", arg.as_str());
+            if let Err(error) = workspace.open_file(Path::new(arg.as_str())) {
+                println!("Can't open {}: {}", arg, error);
+            }
         }
-        let content = fs::read_to_string(Path::new(args[1].as_str())).unwrap();
+        workspace.activate(0);
         Self {
-            text_editor: TextEditor::new(creation_context, content, TextEditorOptions::default()),
+            workspace,
         }
     }
 }
@@ -4,17 +4,73 @@ use std::default::Default;
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
-use glow_glyph::{ab_glyph, GlyphBrush, GlyphBrushBuilder, Section, Text};
+use glow_glyph::{GlyphBrush, GlyphBrushBuilder, Section, Text};
 use eframe::egui::{Color32, Pos2, Sense};
 use eframe::egui::{*};
 use eframe::epaint::{*};
 use eframe::{egui, epaint, emath};
 use glow_glyph::ab_glyph::{PxScale, Font, ScaleFont};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::text_editor::autoscroll::AutoScroll;
+use crate::text_editor::blame::{Blame, BlameGhostTextGap};
+use crate::text_editor::column_selection::{ColumnEdit, ColumnSelection};
+use crate::text_editor::comment::ToggleComment;
+use crate::text_editor::drag_drop::DragDropSelection;
+use crate::text_editor::font::{FontOptions, Fonts, FontSettings, FontStyle};
+use crate::text_editor::git_gutter::{GitBase, GitChanges};
+use crate::text_editor::ime::ImeComposition;
+use crate::text_editor::language::Language;
+use crate::text_editor::line_edit::LineEdit;
+use crate::text_editor::minimap::{Diagnostic, MinimapWidth};
+use crate::text_editor::rulers::LineDecorations;
+use crate::text_editor::split_view::ReplacedRange;
+use crate::text_editor::sticky_scroll::StickyScroll;
+use crate::text_editor::tail::Tail;
+use crate::text_editor::read_only::ProtectedRange;
+use crate::text_editor::mapped_document::{MappedContent, MappedLines};
+use crate::text_editor::events::{ChangeEvents, EventQueue};
+use crate::text_editor::text_transform::TextTransform;
+use crate::text_editor::theme::Theme;
+use crate::text_editor::unicode::{grapheme_width, single_char, UnicodeLine};
+use crate::text_editor::whitespace::WhitespaceRendering;
+
+mod autoscroll;
+mod column_selection;
+mod comment;
+mod diff;
+pub mod diff_view;
+mod drag_drop;
+pub mod events;
+pub mod file_watcher;
+pub mod blame;
+pub mod font;
+pub mod git_gutter;
+mod ime;
+mod line_edit;
+pub mod mapped_document;
+pub mod minimap;
+pub mod read_only;
+mod rulers;
+pub mod split_view;
+mod sticky_scroll;
+pub mod tail;
+#[cfg(test)]
+mod test_support;
+pub mod lines_transform;
+pub mod text_transform;
+pub mod theme;
+mod unicode;
+mod whitespace;
+pub mod workspace;
+pub mod language;
 
 pub struct TextEditor {
     lines: Vec<String>,
     glyph_brush_text_editor: Arc<Mutex<GlyphBrush>>,
     glyph_brush_line_number: Arc<Mutex<GlyphBrush>>,
+    gl: Arc<glow::Context>,
+    fonts: Fonts,
+    zoom: f32,
     scroll_offset: Pos<f32>,
     lines_count: usize,
     char_width: f32,
@@ -22,6 +78,8 @@ pub struct TextEditor {
     scale: f32,
     gutter_width: f32,
     has_pressed_arrow_key: bool,
+    // Only one of the views displayed together (e.g: split view) handles keyboard input
+    has_keyboard_focus: bool,
     text_editor_viewport: Rect,
     // Cursor
     cursor_index: Pos<usize>,
@@ -31,8 +89,47 @@ pub struct TextEditor {
     stop_dragged_index: Option<Pos<usize>>,
     selection_start_index: Option<Pos<usize>>,
     selection_end_index: Option<Pos<usize>>,
+    column_selection_anchor: Option<Pos<usize>>,
+    column_selection_head: Option<Pos<usize>>,
+    // Mouse selection: a drag following a double click extends by word, following a triple click by line.
+    selection_granularity: SelectionGranularity,
+    granularity_anchor: Option<(Pos<usize>, Pos<usize>)>,
+    last_click_time: f64,
+    click_count: usize,
+    // Drag and drop of selected text
+    is_dragging_selection: bool,
+    drop_index: Option<Pos<usize>>,
+    is_mouse_selecting: bool,
+    autoscroll_last_time: Option<f64>,
+    // Input method composition text, not yet committed into lines
+    ime_preedit: Option<String>,
     highlighted_word: Option<String>,
     word_occurrences: RefCell<Vec<(Pos<usize>, Pos<usize>)>>,
+    // Lines of the whole document containing the highlighted word, with the word and edit signature they were found for
+    overview_word_occurrences: RefCell<(Option<String>, (usize, usize, usize), Vec<usize>)>,
+    search_matches: Vec<(Pos<usize>, Pos<usize>)>,
+    // Enclosing scope header lines pinned at the top of the viewport
+    sticky_lines: Vec<usize>,
+    // Scroll requested during a frame, applied on the next one
+    scroll_to_offset_y: Option<f32>,
+    diagnostics: Vec<Diagnostic>,
+    // Backgrounds of whole lines and of grapheme ranges, sorted by line (e.g: diff view)
+    line_backgrounds: Vec<(usize, Color32)>,
+    range_backgrounds: Vec<(Pos<usize>, Pos<usize>, Color32)>,
+    // Line numbers displayed in gutter when they differ from line indexes, None displays no number (e.g: diff filler lines)
+    gutter_line_numbers: Option<Vec<Option<usize>>>,
+    // Markers of lines changed since the git index or HEAD version
+    git_changes: GitChanges,
+    blame: Blame,
+    // Followed file, its appended data is added to lines
+    tail: Option<Tail>,
+    // Memory mapped document lines are read from, None when content is in lines
+    mapped_content: Option<MappedContent>,
+    protected_ranges: Vec<ProtectedRange>,
+    // Edits not yet seen by the other view of a split view, None outside of a split view
+    view_edits: Option<Vec<ReplacedRange>>,
+    change_events: ChangeEvents,
+    event_queue: EventQueue,
     // matching open-close characters
     opening_char: RefCell<Option<char>>,
     closing_char: RefCell<Option<char>>,
@@ -41,7 +138,19 @@ pub struct TextEditor {
     unsaved_stated: Option<UnsavedState>,
     history: Vec<State>,
     history_index: usize,
+    // History length when content was last saved, None when undo went before it.
+    saved_history_len: Option<usize>,
+    // History length right after the last column selection edit, the next one can be merged into it.
+    column_edit_history_len: Option<usize>,
     latest_change_time: f32,
+    options: TextEditorOptions,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SelectionGranularity {
+    Char,
+    Word,
+    Line,
 }
 
 #[derive(Clone, Debug)]
@@ -74,7 +183,7 @@ pub struct AddCharAction {
 #[derive(Default, Clone, Debug)]
 pub struct RemoveCharAction {
     start_pos: Pos<usize>,
-    char: char,
+    char: String,
 }
 
 #[derive(Default, Clone, Debug)]
@@ -99,7 +208,7 @@ pub struct State {
     bulk_action: BulkAction,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct Pos<T> {
     pub x: T,
     pub y: T,
@@ -109,6 +218,26 @@ pub struct Pos<T> {
 pub struct TextEditorOptions {
     pub scale_factor: f32,
     pub font_size: f32,
+    pub font: FontOptions,
+    pub language: Language,
+    // Display spaces, tabs and line ends
+    pub render_whitespace: bool,
+    pub indent_guides: bool,
+    pub indent_size: usize,
+    pub highlight_current_line: bool,
+    // Columns at which a vertical ruler is displayed
+    pub rulers: Vec<usize>,
+    pub minimap: bool,
+    pub sticky_scroll: bool,
+    // Version compared to for gutter change markers of files opened from a path, None disables markers
+    pub git_base: Option<GitBase>,
+    // Blame of the cursor line displayed after its end, for files opened from a path
+    pub inline_blame: bool,
+    // Content can be navigated, selected and copied but not edited
+    pub read_only: bool,
+    // Edits, cursor, selection, save, dirty state and scroll changes are queued until take_events is called
+    pub emit_events: bool,
+    pub theme: Theme,
 }
 
 impl Default for TextEditorOptions {
@@ -116,49 +245,76 @@ impl Default for TextEditorOptions {
         Self {
             scale_factor: 1.0,
             font_size: 12.0,
+            font: FontOptions::default(),
+            language: Language::default(),
+            render_whitespace: false,
+            indent_guides: false,
+            indent_size: 4,
+            highlight_current_line: true,
+            rulers: vec![],
+            minimap: false,
+            sticky_scroll: false,
+            git_base: None,
+            inline_blame: false,
+            read_only: false,
+            emit_events: false,
+            theme: Theme::default(),
         }
     }
 }
 // const scale_factor: f32 = 1.5;
 impl TextEditor {
-    pub fn new(creation_context: &eframe::CreationContext<'_>, text: String, options: TextEditorOptions) -> Self {
-        let font = ab_glyph::FontArc::try_from_slice(include_bytes!(
-            "Inconsolata-Regular.ttf"
-        )).unwrap();
+    pub fn text(&self) -> String {
+        match self.mapped_document() {
+            Some(document) => document.text(),
+            None => self.lines.join("\n"),
+        }
+    }
 
-        let glyph_brush = Arc::new(Mutex::new(GlyphBrushBuilder::using_font(font.clone())
-            .initial_cache_size((2048 * 2, 2048 * 2))
-            .draw_cache_position_tolerance(1.0)
-            .build(creation_context.gl.as_ref().unwrap().as_ref())));
-        let glyph_brush_line_number = Arc::new(Mutex::new(GlyphBrushBuilder::using_font(font.clone())
-            .initial_cache_size((120, 120))
-            .draw_cache_position_tolerance(1.0)
-            .build(creation_context.gl.as_ref().unwrap().as_ref())));
+    // Dirty when history has changed since the last save, or when typing is not yet recorded in history.
+    pub fn is_dirty(&self) -> bool {
+        self.unsaved_stated.is_some() || self.saved_history_len != Some(self.history.len())
+    }
+
+    // Changes when lines are edited: history length, pending actions count and lines count.
+    pub(crate) fn edit_signature(&self) -> (usize, usize, usize) {
+        (self.history.len(), self.unsaved_stated.as_ref().map_or(0, |state| state.actions.len()), self.lines.len())
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit_unsaved_state();
+        self.saved_history_len = Some(self.history.len());
+        self.on_saved();
+    }
+
+    pub fn new(creation_context: &eframe::CreationContext<'_>, text: String, options: TextEditorOptions) -> Self {
+        let gl = creation_context.gl.as_ref().unwrap().clone();
+        let fonts = Fonts::load(&options.font);
+        let glyph_brushes = Self::build_glyph_brushes(gl.as_ref(), &fonts);
+        Self::with_shared_resources(gl, fonts, glyph_brushes, text, options)
+    }
 
+    // Glyph brushes hold GL textures, editors displayed one at a time (e.g: workspace documents) can share them.
+    pub(crate) fn with_shared_resources(gl: Arc<glow::Context>, fonts: Fonts, glyph_brushes: (Arc<Mutex<GlyphBrush>>, Arc<Mutex<GlyphBrush>>),
+                                        text: String, options: TextEditorOptions) -> Self {
+        let (glyph_brush, glyph_brush_line_number) = glyph_brushes;
         let split = text.split("\n").map(|s| s.to_string()).collect::<Vec<String>>();
         let lines_count = split.len();
-        let font_size = options.font_size;
-        let scale_factor = options.scale_factor;
-        let scale = font_size * scale_factor;
-
-        let scale_font = font.as_scaled(PxScale { x: scale, y: scale }); // y scale has not impact
-        let width = scale_font.h_advance(font.glyph_id('W'));
-        let height = scale_font.height();
-        let line_gap = scale_font.line_gap();
-        let char_width = width;
-        let line_height = height;
-        println!("char height: {}, width {}, gap: {}, scale_factor: {}", height, width, line_gap, scale_factor);
-        Self {
+        let mut text_editor = Self {
             lines: split,
             glyph_brush_text_editor: glyph_brush,
             glyph_brush_line_number,
+            gl,
+            fonts,
+            zoom: 1.0,
             scroll_offset: Default::default(),
             lines_count,
-            char_width,
-            line_height,
-            scale,
+            char_width: 0.0,
+            line_height: 0.0,
+            scale: 0.0,
             gutter_width: 0.0,
             has_pressed_arrow_key: false,
+            has_keyboard_focus: true,
             text_editor_viewport: Rect { min: Pos2::default(), max: Pos2::default() },
             cursor_index: Default::default(),
             cursor_pos: Default::default(),
@@ -166,8 +322,35 @@ impl TextEditor {
             stop_dragged_index: Default::default(),
             selection_start_index: Default::default(),
             selection_end_index: Default::default(),
+            column_selection_anchor: None,
+            column_selection_head: None,
+            selection_granularity: SelectionGranularity::Char,
+            granularity_anchor: None,
+            last_click_time: 0.0,
+            click_count: 0,
+            is_dragging_selection: false,
+            drop_index: None,
+            is_mouse_selecting: false,
+            autoscroll_last_time: None,
+            ime_preedit: None,
             highlighted_word: None,
             word_occurrences: RefCell::new(vec![]),
+            overview_word_occurrences: RefCell::new((None, (0, 0, 0), vec![])),
+            search_matches: vec![],
+            sticky_lines: vec![],
+            scroll_to_offset_y: None,
+            diagnostics: vec![],
+            line_backgrounds: vec![],
+            range_backgrounds: vec![],
+            gutter_line_numbers: None,
+            git_changes: GitChanges::default(),
+            blame: Blame::default(),
+            tail: None,
+            mapped_content: None,
+            protected_ranges: vec![],
+            view_edits: None,
+            change_events: ChangeEvents::default(),
+            event_queue: EventQueue::default(),
             opening_char: RefCell::new(None),
             closing_char: RefCell::new(None),
             opening_char_index: RefCell::new(None),
@@ -175,20 +358,64 @@ impl TextEditor {
             unsaved_stated: None,
             history: vec![],
             history_index: 0,
+            saved_history_len: Some(0),
+            column_edit_history_len: None,
             latest_change_time: 0.0,
-        }
+            options,
+        };
+        text_editor.update_font_metrics();
+        text_editor
+    }
+
+    pub(crate) fn build_glyph_brushes(gl: &glow::Context, fonts: &Fonts) -> (Arc<Mutex<GlyphBrush>>, Arc<Mutex<GlyphBrush>>) {
+        let glyph_brush = Arc::new(Mutex::new(GlyphBrushBuilder::using_fonts(fonts.fonts.clone())
+            .initial_cache_size((2048 * 2, 2048 * 2))
+            .draw_cache_position_tolerance(1.0)
+            .build(gl)));
+        let glyph_brush_line_number = Arc::new(Mutex::new(GlyphBrushBuilder::using_font(fonts.regular().clone())
+            .initial_cache_size((120, 120))
+            .draw_cache_position_tolerance(1.0)
+            .build(gl)));
+        (glyph_brush, glyph_brush_line_number)
+    }
+
+    fn rebuild_glyph_brushes(&mut self) {
+        let (glyph_brush, glyph_brush_line_number) = Self::build_glyph_brushes(self.gl.as_ref(), &self.fonts);
+        self.glyph_brush_text_editor = glyph_brush;
+        self.glyph_brush_line_number = glyph_brush_line_number;
+    }
+
+    // Char width and line height are derived from the regular font, at the current font size and zoom.
+    fn update_font_metrics(&mut self) {
+        let font = self.fonts.regular();
+        let scale = self.options.font_size * self.zoom * self.options.scale_factor;
+        let scale_font = font.as_scaled(PxScale { x: scale, y: scale }); // y scale has not impact
+        let width = scale_font.h_advance(font.glyph_id('W'));
+        let height = scale_font.height();
+        self.char_width = width;
+        self.line_height = height;
+        self.scale = scale;
+        // Cursor position and scroll depend on metrics, cursor is kept visible.
+        self.cursor_pos.y = self.index_to_y(self.cursor_index.y);
+        self.cursor_pos.x = self.index_to_x(&self.cursor_index);
+        self.has_pressed_arrow_key = true;
     }
 
     pub fn ui(&mut self, ctx: &egui::Context, ui: &mut egui::Ui) {
         if self.lines.len() == 0 {
             self.lines.push(String::default());
         }
+        self.update_mapped_lines();
         self.lines_count = self.lines.len();
+        // Ctrl +, ctrl - and ctrl 0 zoom the text editor, egui would also scale the whole ui with them.
+        ctx.options_mut(|options| options.zoom_with_keyboard = false);
 
         // We implement a virtual scroll, the viewport rect is static.
         let viewport = ui.max_rect();
         // Gutter display line numbers
-        self.gutter_width = (TextEditor::count_digit(self.lines_count).max(1) as f32 * self.char_width);
+        self.update_git_changes();
+        self.update_blame(ui);
+        self.gutter_width = (TextEditor::count_digit(self.lines_count).max(1) as f32 * self.char_width) + self.git_marker_width();
         // Gutter
         let gutter_rect = Rect { min: Pos2 { x: viewport.min.x, y: viewport.min.y }, max: Pos2 { x: viewport.min.x + self.gutter_width, y: viewport.max.y } };
 
@@ -197,6 +424,30 @@ impl TextEditor {
 
         self.text_editor_viewport = viewport;
         self.text_editor_viewport.min.x = gutter_rect.max.x;
+        // Minimap is on the right, before the vertical scroll bar
+        let maybe_minimap_rect = if self.options.minimap {
+            let right = viewport.max.x - ui.spacing().scroll.allocated_width();
+            self.text_editor_viewport.max.x = right - MinimapWidth;
+            Some(Rect { min: Pos2 { x: right - MinimapWidth, y: viewport.min.y }, max: Pos2 { x: right, y: viewport.max.y } })
+        } else {
+            None
+        };
+        self.update_tail(ui);
+        if let Some(scroll_offset_y) = self.scroll_to_offset_y.take() {
+            self.scroll_offset.y = scroll_offset_y;
+            scroll_area = scroll_area.vertical_scroll_offset(self.scroll_offset.y);
+        }
+        if let Some(scroll_offset_y) = maybe_minimap_rect.and_then(|minimap_rect| self.minimap_interaction(ui, minimap_rect)) {
+            self.scroll_offset.y = scroll_offset_y;
+            scroll_area = scroll_area.vertical_scroll_offset(self.scroll_offset.y);
+        }
+        let maybe_autoscroll = self.autoscroll_delta(ui);
+        if maybe_autoscroll.is_some() {
+            let autoscroll = maybe_autoscroll.unwrap();
+            self.scroll_offset.y = (self.scroll_offset.y + autoscroll.y).max(0.0).min(self.lines_count as f32 * self.line_height);
+            self.scroll_offset.x = (self.scroll_offset.x + autoscroll.x).max(0.0);
+            scroll_area = scroll_area.vertical_scroll_offset(self.scroll_offset.y).horizontal_scroll_offset(self.scroll_offset.x);
+        }
         let text_editor_viewport_height = (self.text_editor_viewport.max.y - self.text_editor_viewport.min.y);
         let text_editor_viewport_width = (self.text_editor_viewport.max.x - self.text_editor_viewport.min.x);
         let max_lines = (text_editor_viewport_height / self.line_height);
@@ -206,13 +457,15 @@ impl TextEditor {
         if self.has_pressed_arrow_key {
             self.has_pressed_arrow_key = false;
             // when cursor is not more visible in the viewport, we want to scroll to it
+            // rows under pinned scope headers are not visible
+            let sticky_lines_height = self.sticky_lines.len() as f32 * self.line_height;
             let cursor_offset_y = self.scroll_offset.y + text_editor_viewport_height - self.line_height - self.cursor_pos.y;
             if cursor_offset_y < 0.0 {
                 let mut hidden_lines: f32 = ((cursor_offset_y.abs() / self.line_height) as usize + 1) as f32;
                 self.scroll_offset.y += hidden_lines * self.line_height;
                 scroll_area = scroll_area.vertical_scroll_offset(self.scroll_offset.y);
-            } else if cursor_offset_y > text_editor_viewport_height {
-                let mut hidden_lines: f32 = (((cursor_offset_y - text_editor_viewport_height) / self.line_height) as usize + 1) as f32;
+            } else if cursor_offset_y > text_editor_viewport_height - sticky_lines_height {
+                let mut hidden_lines: f32 = (((cursor_offset_y - text_editor_viewport_height + sticky_lines_height) / self.line_height) as usize + 1) as f32;
                 self.scroll_offset.y -= hidden_lines * self.line_height;
                 scroll_area = scroll_area.vertical_scroll_offset(self.scroll_offset.y);
             }
@@ -230,8 +483,12 @@ impl TextEditor {
         if self.cursor_index.x == 0 && self.cursor_pos.x != self.text_editor_viewport.min.x {
             self.cursor_pos.x = self.text_editor_viewport.min.x;
         }
+        self.update_sticky_lines(first_line_index);
         // Gutter
         self.gutter(ui, gutter_rect, first_line_index, last_line_index);
+        self.paint_mapped_load_progress(ui);
+        self.git_gutter_interaction(ui, gutter_rect, first_line_index);
+        self.git_hunk_popup(ui, gutter_rect, first_line_index);
 
         let output = scroll_area.show(ui, |ui| {
             ui.set_min_width(ui.available_width());
@@ -244,6 +501,8 @@ impl TextEditor {
                     let mut max_char_count = 0;
                     let mut opening_char_occurrence = 0;
                     self.word_occurrences = RefCell::new(vec![]);
+                    let maybe_blame_ghost_text = self.blame_ghost_text();
+                    let blame_color = self.options.theme.blame_text.to_array().map(|component| component as f32 / 255.0);
                     opening_char_occurrence = self.find_opening_matching_char(first_line_index, last_line_index, opening_char_occurrence);
 
 
@@ -254,34 +513,62 @@ impl TextEditor {
                         // self.paint_debug_char(self.text_editor_viewport.min.y, &mut shapes, relative_line_index, absolute_line_index, frag);
 
                         opening_char_occurrence = self.find_closing_matching_char(opening_char_occurrence, frag, absolute_line_index);
-                        if max_char_count < frag.len() {
-                            max_char_count = frag.len();
+                        let columns_count = frag.columns_count();
+                        if max_char_count < columns_count {
+                            max_char_count = columns_count;
+                        }
+                        if relative_line_index < self.sticky_lines.len() {
+                            // line is hidden by a pinned scope header
+                            text_line.push((format!("{}\n", self.lines[self.sticky_lines[relative_line_index]]), TextColor, FontStyle::Bold));
+                        } else if let Some(blame_ghost_text) = maybe_blame_ghost_text.as_ref().filter(|_| absolute_line_index == self.cursor_index.y) {
+                            text_line.push((self.line_with_preedit(frag, absolute_line_index).to_string(), TextColor, FontStyle::Regular));
+                            text_line.push((format!("{}{}\n", BlameGhostTextGap, blame_ghost_text), blame_color, FontStyle::Italic));
+                        } else {
+                            text_line.push((format!("{}\n", self.line_with_preedit(frag, absolute_line_index)), TextColor, FontStyle::Regular));
                         }
-                        text_line.push(format!("{}\n", frag));
                     }
 
                     let mut brush_mut = self.glyph_brush_text_editor.as_ref().lock().unwrap();
                     let section = glow_glyph::Section {
                         screen_position: (0.0 - self.scroll_offset.x, 0.0),
-                        text: text_line.iter().map(|line| {
-                            Text::default().with_text(&line).with_color([0.0, 0.0, 0.0, 1.0]).with_scale(self.scale * 2.0)
-                        }).collect::<Vec<Text>>(),
+                        text: text_line.iter().flat_map(|(line, color, style)| self.fonts.runs(line, *style).into_iter().map(move |run| (run, color)))
+                            .map(|((run, font_id), color)| {
+                                Text::default().with_text(run).with_font_id(font_id).with_color(*color).with_scale(self.scale * 2.0)
+                            }).collect::<Vec<Text>>(),
                         layout: glow_glyph::Layout::default_wrap(),
                         ..Section::default()
                     };
                     brush_mut.queue(section);
                     drop(brush_mut);
 
+                    self.paint_line_backgrounds(first_line_index, last_line_index, &mut shapes);
+                    if self.options.highlight_current_line {
+                        self.paint_current_line(first_line_index, &mut shapes);
+                    }
+                    self.paint_rulers(&mut shapes);
+                    if self.options.indent_guides {
+                        self.paint_indent_guides(first_line_index, last_line_index, &mut shapes);
+                    }
+                    if self.options.render_whitespace {
+                        self.paint_whitespaces(first_line_index, last_line_index, &mut shapes);
+                    }
                     // Paint text selection
                     shapes.extend(self.selection_shapes(first_line_index));
                     // Paint cursor
                     if self.cursor_index.y >= first_line_index {
                         shapes.push(self.cursor_shape(first_line_index));
                     }
+                    if let Some(drop_caret) = self.drop_caret_shape(first_line_index) {
+                        shapes.push(drop_caret);
+                    }
+                    if let Some(preedit_underline) = self.preedit_underline_shape(first_line_index) {
+                        shapes.push(preedit_underline);
+                    }
                     // Paint matching {},[],() highlight
                     self.paint_matching_opening_closing_char(first_line_index, &mut shapes);
 
                     self.paint_word_occurrences(first_line_index, &mut shapes);
+                    shapes.extend(self.sticky_scroll_shapes());
 
                     ui.painter().extend(shapes);
 
@@ -309,13 +596,22 @@ impl TextEditor {
                     if response.double_clicked() {
                         self.on_double_click(ui);
                     }
+                    if response.triple_clicked() {
+                        self.on_triple_click(ui);
+                    }
                     if response.drag_started() {
                         self.on_drag_start(ui);
                     }
                     if response.dragged() {
                         self.on_drag(ui);
                     }
-                    self.handle_key_events(&ui, &ui.input(|input| input.events.clone()).as_ref());
+                    if response.drag_stopped() {
+                        self.on_drag_stop(ui);
+                    }
+                    if self.has_keyboard_focus {
+                        self.handle_key_events(&ui, &ui.input(|input| input.events.clone()).as_ref());
+                        self.update_ime_output(ui, first_line_index);
+                    }
                     ui.set_min_width(self.gutter_width + (self.char_width) * max_char_count as f32);
                     response
                 },
@@ -326,8 +622,12 @@ impl TextEditor {
             self.scroll_offset.x = output.state.offset.x;
             self.set_cursor_x(self.cursor_index.x);
         }
+        if let Some(minimap_rect) = maybe_minimap_rect {
+            self.paint_minimap(ui, minimap_rect);
+        }
 
         self.feed_history(ui);
+        self.update_change_events();
     }
 
     fn find_closing_matching_char(&self, mut opening_char_occurrence: i32, frag: &String, absolute_line_index: usize) -> i32 {
@@ -336,10 +636,15 @@ impl TextEditor {
             let opening_char = self.opening_char.borrow().unwrap();
             let opening_char_index = opening_char_index_ref.as_ref().unwrap();
             if absolute_line_index >= opening_char_index.y {
-                for (i, c) in frag.chars().enumerate() {
+                for (i, grapheme) in frag.graphemes(true).enumerate() {
                     if opening_char_index.y == absolute_line_index && i < opening_char_index.x {
                         continue;
                     };
+                    let maybe_char = single_char(grapheme);
+                    if maybe_char.is_none() {
+                        continue;
+                    }
+                    let c = maybe_char.unwrap();
                     if c == opening_char {
                         opening_char_occurrence += 1;
                     } else if Self::matching_closing_char(opening_char) == c {
@@ -368,10 +673,16 @@ impl TextEditor {
                     let closing_char_index_ref = self.closing_char_index.borrow();
                     let closing_char_index = closing_char_index_ref.as_ref().unwrap();
                     if absolute_line_index <= closing_char_index.y {
-                        for (i, c) in frag.chars().rev().enumerate() {
-                            if closing_char_index.y == absolute_line_index && frag.len() - i > closing_char_index.x {
+                        let graphemes_count = frag.grapheme_count();
+                        for (i, grapheme) in frag.graphemes(true).rev().enumerate() {
+                            if closing_char_index.y == absolute_line_index && graphemes_count - i > closing_char_index.x {
                                 continue;
                             }
+                            let maybe_char = single_char(grapheme);
+                            if maybe_char.is_none() {
+                                continue;
+                            }
+                            let c = maybe_char.unwrap();
                             if c == self.closing_char.borrow().unwrap() {
                                 opening_char_occurrence += 1;
                             } else if Self::matching_opening_char(self.closing_char.borrow().unwrap()) == c {
@@ -380,7 +691,7 @@ impl TextEditor {
                             if Self::matching_opening_char(self.closing_char.borrow().unwrap()) == c && opening_char_occurrence == 0 {
                                 *self.opening_char.borrow_mut() = Some(c);
                                 *self.opening_char_index.borrow_mut() = Some(Pos {
-                                    x: frag.len() - i - 1,
+                                    x: graphemes_count - i - 1,
                                     y: absolute_line_index,
                                 });
                                 break;
@@ -394,33 +705,10 @@ impl TextEditor {
     }
 
     fn highlight_word_occurrences(&self, frag: &String, absolute_line_index: usize) {
-        let mut should_search_word = false;
-        let mut word_char_search_index = 0;
-        let mut start_index = 0;
         if self.highlighted_word.is_some() {
-            let highlighted_word = self.highlighted_word.as_ref().unwrap();
-            for (i, c) in frag.chars().enumerate() {
-                if Self::is_char_non_part_of_word(c) {
-                    if word_char_search_index == highlighted_word.len() {
-                        self.word_occurrences.borrow_mut().push((Pos { x: start_index + 1, y: absolute_line_index }, Pos { x: i, y: absolute_line_index }));
-                    }
-                    should_search_word = true;
-                    word_char_search_index = 0;
-                    start_index = i;
-                    continue;
-                }
-                if should_search_word {
-                    let word_char = highlighted_word.chars().nth(word_char_search_index);
-                    if word_char.is_some() && word_char.unwrap() == c {
-                        word_char_search_index += 1;
-                    } else {
-                        word_char_search_index = 0;
-                        should_search_word = false;
-                    }
-                }
-            }
-            if word_char_search_index == highlighted_word.len() {
-                self.word_occurrences.borrow_mut().push((Pos { x: start_index + 1, y: absolute_line_index }, Pos { x: frag.chars().count(), y: absolute_line_index }));
+            let highlighted_word = self.highlighted_word.as_ref().unwrap().graphemes(true).collect::<Vec<&str>>();
+            for (start_x, end_x) in word_occurrences_in_line(&highlighted_word, frag) {
+                self.word_occurrences.borrow_mut().push((Pos { x: start_x, y: absolute_line_index }, Pos { x: end_x, y: absolute_line_index }));
             }
         }
     }
@@ -428,83 +716,287 @@ impl TextEditor {
     fn on_drag(&mut self, ui: &mut Ui) {
         let maybe_pos = ui.input(|input| input.pointer.interact_pos());
         let cursor_pos = maybe_pos.unwrap();
-        self.stop_dragged_index = Some(Pos::<usize> { x: self.x_to_index(cursor_pos.x - self.line_x_offset()), y: self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y) });
+        // Pointer can be outside the viewport while autoscrolling, selection stops at the visible edge.
+        let cursor_pos = Pos2 {
+            x: cursor_pos.x.max(self.text_editor_viewport.min.x),
+            y: cursor_pos.y.max(self.text_editor_viewport.min.y).min(self.text_editor_viewport.max.y - 1.0),
+        };
+        if self.has_column_selection() {
+            self.extend_column_selection(self.pointer_to_column(cursor_pos));
+            return;
+        }
+        if self.is_dragging_selection {
+            self.on_selection_drag(ui, self.pointer_to_index(cursor_pos));
+            return;
+        }
+        if self.selection_granularity != SelectionGranularity::Char && self.granularity_anchor.is_some() {
+            let index = self.pointer_to_index(cursor_pos);
+            let (anchor_start, anchor_end) = self.granularity_anchor.clone().unwrap();
+            let (range_start, range_end) = self.granularity_range_at(index);
+            // selection always contains the word or line initially clicked
+            let (start_index, end_index, cursor) = if Self::is_before(&range_start, &anchor_start) {
+                (anchor_end, range_start.clone(), range_start)
+            } else {
+                (anchor_start, range_end.clone(), range_end)
+            };
+            self.select_range(start_index, end_index);
+            self.set_cursor_y(cursor.y);
+            self.set_cursor_x(cursor.x);
+            return;
+        }
+        let index = self.pointer_to_index(cursor_pos);
+        self.stop_dragged_index = Some(index.clone());
         self.set_selection();
-        self.set_cursor_x(self.x_to_index(cursor_pos.x - (self.line_x_offset())));
-        self.set_cursor_y(self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y));
+        self.set_cursor_y(index.y);
+        self.set_cursor_x(index.x);
     }
 
     fn on_drag_start(&mut self, ui: &mut Ui) {
         let maybe_pos = ui.input(|input| input.pointer.interact_pos());
         let cursor_pos = maybe_pos.unwrap();
-        self.start_dragged_index = Some(Pos::<usize> { x: self.x_to_index(cursor_pos.x - self.line_x_offset()), y: self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y) });
+        self.is_mouse_selecting = true;
+        if ui.input(|input| input.modifiers.alt) {
+            self.start_column_selection(self.pointer_to_column(cursor_pos));
+            return;
+        }
+        self.column_selection_anchor = None;
+        self.column_selection_head = None;
+        let index = self.pointer_to_index(cursor_pos);
+        // A drag starting quickly after a click comes from a double (or triple) press, it selects by word (or line).
+        let press_time = ui.input(|input| input.pointer.press_start_time().unwrap_or(input.time));
+        self.selection_granularity = if press_time - self.last_click_time >= DoubleClickDelay {
+            SelectionGranularity::Char
+        } else if self.click_count >= 2 {
+            SelectionGranularity::Line
+        } else {
+            SelectionGranularity::Word
+        };
+        let press_origin = ui.input(|input| input.pointer.press_origin()).unwrap_or(cursor_pos);
+        if self.selection_granularity == SelectionGranularity::Char && self.is_pointer_on_selection(press_origin) {
+            self.start_selection_drag();
+            return;
+        }
+        if self.selection_granularity != SelectionGranularity::Char {
+            let (start_index, end_index) = self.granularity_range_at(index);
+            self.select_range(start_index.clone(), end_index.clone());
+            self.granularity_anchor = Some((start_index, end_index));
+            return;
+        }
+        self.granularity_anchor = None;
+        self.start_dragged_index = Some(index);
         self.stop_dragged_index = None;
     }
 
+    fn on_drag_stop(&mut self, ui: &mut Ui) {
+        self.is_mouse_selecting = false;
+        self.autoscroll_last_time = None;
+        if self.is_dragging_selection {
+            self.on_selection_drop(ui);
+        }
+    }
+
+    fn granularity_range_at(&self, index: Pos<usize>) -> (Pos<usize>, Pos<usize>) {
+        if self.selection_granularity == SelectionGranularity::Line {
+            return self.line_range_at(index.y);
+        }
+        let (start_x, end_x) = self.word_range_at(index.y, index.x);
+        (Pos { x: start_x, y: index.y }, Pos { x: end_x, y: index.y })
+    }
+
+    // Whole line including its line break, the last line has no line break.
+    fn line_range_at(&self, y_index: usize) -> (Pos<usize>, Pos<usize>) {
+        if y_index + 1 < self.lines.len() {
+            (Pos { x: 0, y: y_index }, Pos { x: 0, y: y_index + 1 })
+        } else {
+            (Pos { x: 0, y: y_index }, Pos { x: self.lines[y_index].grapheme_count(), y: y_index })
+        }
+    }
+
+    #[inline]
+    fn is_before(a: &Pos<usize>, b: &Pos<usize>) -> bool {
+        a.y < b.y || (a.y == b.y && a.x < b.x)
+    }
+
     fn on_click(&mut self, ui: &mut Ui) {
+        let time = ui.input(|input| input.time);
+        if time - self.last_click_time < DoubleClickDelay {
+            self.click_count += 1;
+        } else {
+            self.click_count = 1;
+        }
+        self.last_click_time = time;
         let maybe_pos = ui.input(|input| input.pointer.interact_pos());
         if maybe_pos.is_some() {
             let cursor_pos = maybe_pos.unwrap();
-            self.set_cursor_x(self.x_to_index(cursor_pos.x - (self.line_x_offset())));
-            self.set_cursor_y(self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y));
+            if let Some((row, line_index)) = self.sticky_line_at(cursor_pos) {
+                // Jump to the pinned header, scrolled so it stays at the same place.
+                self.reset_selection();
+                self.set_cursor_y(line_index);
+                self.set_cursor_x(0);
+                self.scroll_to_offset_y = Some(line_index.saturating_sub(row) as f32 * self.line_height);
+                return;
+            }
+            if ui.input(|input| input.modifiers.shift) {
+                // Shift + click extends the selection from its start, or from the cursor
+                if self.start_dragged_index.is_none() {
+                    self.start_dragged_index = Some(self.selection_start_index.clone().unwrap_or(self.cursor_index.clone()));
+                }
+                let index = self.pointer_to_index(cursor_pos);
+                self.stop_dragged_index = Some(index.clone());
+                self.set_selection();
+                self.set_cursor_y(index.y);
+                self.set_cursor_x(index.x);
+                return;
+            }
+            let index = self.pointer_to_index(cursor_pos);
+            self.set_cursor_y(index.y);
+            self.set_cursor_x(index.x);
             self.reset_selection();
         }
     }
 
+    fn on_triple_click(&mut self, ui: &mut Ui) {
+        let maybe_pos = ui.input(|input| input.pointer.interact_pos());
+        if maybe_pos.is_some() {
+            let cursor_pos = maybe_pos.unwrap();
+            let y_index = self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y).min(self.lines.len() - 1);
+            let (start_index, end_index) = self.line_range_at(y_index);
+            self.select_range(start_index, end_index.clone());
+            self.set_cursor_y(end_index.y);
+            self.set_cursor_x(end_index.x);
+        }
+    }
+
     fn on_double_click(&mut self, ui: &mut Ui) {
         let maybe_pos = ui.input(|input| input.pointer.interact_pos());
         if maybe_pos.is_some() {
             let cursor_pos = maybe_pos.unwrap();
-            let y_index = self.y_to_index(cursor_pos.y - self.text_editor_viewport.min.y);
+            let index = self.pointer_to_index(cursor_pos);
+            let y_index = index.y;
+            let (start_index, end_index) = self.word_range_at(y_index, index.x);
             let line = self.lines[y_index].as_str();
-            let x_index = self.x_to_index(cursor_pos.x - (self.line_x_offset()));
-            let mut start_index = 0 as usize;
-            let mut end_index = 0 as usize;
-            for (i, c) in line.chars().enumerate() {
-                if Self::is_char_non_part_of_word(c) {
-                    if i >= x_index {
-                        end_index = i;
-                        break;
-                    } else {
-                        start_index = i + 1;
-                    }
-                }
-            }
-            if end_index == 0 {
-                end_index = line.len();
-            }
             self.selection_start_index = Some(Pos { x: start_index, y: y_index });
             self.selection_end_index = Some(Pos { x: end_index, y: y_index });
             if end_index - start_index > 1 {
-                self.highlighted_word = Some((&line[start_index..end_index]).to_string());
+                self.highlighted_word = Some((&line[line.byte_index_from_grapheme_index(start_index)..line.byte_index_from_grapheme_index(end_index)]).to_string());
             }
             self.set_cursor_x(end_index);
         }
     }
 
+    fn word_range_at(&self, y_index: usize, x_index: usize) -> (usize, usize) {
+        let line = self.lines[y_index].as_str();
+        let mut start_index = 0 as usize;
+        let mut end_index = None;
+        for (i, grapheme) in line.graphemes(true).enumerate() {
+            if Self::is_grapheme_non_part_of_word(grapheme) {
+                if i >= x_index {
+                    end_index = Some(i);
+                    break;
+                } else {
+                    start_index = i + 1;
+                }
+            }
+        }
+        (start_index, end_index.unwrap_or_else(|| line.grapheme_count()))
+    }
+
     fn is_char_non_part_of_word(c: char) -> bool {
         !c.is_alphanumeric() && c != '_' && c != '-'
     }
 
+    fn is_grapheme_non_part_of_word(grapheme: &str) -> bool {
+        // graphemes made of several chars are letters with combining marks, or emojis
+        single_char(grapheme).map_or(false, Self::is_char_non_part_of_word)
+    }
+
     fn handle_key_events(&mut self, ui: &Ui, events: &Vec<Event>) {
         for event in events {
+            // a previous event may have moved the cursor or the selection to lines not read yet
+            self.update_mapped_lines();
             match event {
+                // While composing, keys are handled by the input method (e.g: enter validates a candidate)
+                Event::Key { pressed: true, .. } if self.is_composing() => {}
                 Event::Key { key, pressed: true, modifiers, .. } => self.on_key_press(ui, *key, modifiers),
-                Event::Text(text_to_insert) => {
-                    if self.has_selection() {
-                        self.key_press_on_selection(Some(text_to_insert));
+                Event::CompositionStart | Event::CompositionUpdate(_) | Event::CompositionEnd(_) if self.is_read_only() => {}
+                Event::Text(text_to_insert) => self.insert_typed_text(ui, text_to_insert),
+                Event::CompositionStart => self.on_composition_update(""),
+                Event::CompositionUpdate(text) => self.on_composition_update(text),
+                Event::CompositionEnd(text) => self.on_composition_end(ui, text),
+                Event::Copy | Event::Cut => {
+                    let text = if self.has_column_selection() {
+                        self.column_selection_text()
                     } else {
-                        self.insert_text_at(text_to_insert, self.cursor_index.clone());
+                        self.selected_text()
+                    };
+                    if text.is_empty() {
+                        continue;
+                    }
+                    ui.output_mut(|output| output.copied_text = text);
+                    if matches!(event, Event::Cut) {
+                        self.delete_selected_text(ui);
                     }
-                    self.push_action_to_unsaved_state(&ui, SingleAction::AddChar(AddCharAction { start_pos: self.cursor_index.clone(), char: text_to_insert.clone() }
-                    ));
-                    self.set_cursor_x(self.cursor_index.x + 1);
+                }
+                Event::Paste(text_to_insert) => {
+                    if self.has_column_selection() {
+                        self.edit_column_selection(ui, ColumnEdit::Insert(text_to_insert));
+                        continue;
+                    }
+                    let (start_index, end_index) = if self.has_selection() {
+                        (self.selection_start_index.clone().unwrap(), self.selection_end_index.clone().unwrap())
+                    } else {
+                        (self.cursor_index.clone(), self.cursor_index.clone())
+                    };
+                    let new_end_index = match self.replace_text(ui, &start_index, &end_index, text_to_insert.replace("\r\n", "\n").as_str()) {
+                        Some(new_end_index) => new_end_index,
+                        None => continue,
+                    };
+                    self.reset_selection();
+                    self.has_pressed_arrow_key = true;
+                    self.set_cursor_y(new_end_index.y);
+                    self.set_cursor_x(new_end_index.x);
                 }
                 _ => {}
             }
         }
     }
 
+    fn insert_typed_text(&mut self, ui: &Ui, text_to_insert: &str) {
+        if self.has_column_selection() {
+            self.edit_column_selection(ui, ColumnEdit::Insert(text_to_insert));
+            return;
+        }
+        if self.has_selection() && !self.key_press_on_selection(None) {
+            return;
+        }
+        if !self.is_edit_allowed(&self.cursor_index, &self.cursor_index) {
+            return;
+        }
+        let previous_grapheme_count = self.lines[self.cursor_index.y].grapheme_count();
+        self.insert_text_at(text_to_insert, self.cursor_index.clone());
+        self.push_action_to_unsaved_state(&ui, SingleAction::AddChar(AddCharAction { start_pos: self.cursor_index.clone(), char: text_to_insert.to_string() }
+        ));
+        // a combining mark joins the previous grapheme, cursor moves by the number of new graphemes only.
+        let inserted_grapheme_count = self.lines[self.cursor_index.y].grapheme_count() - previous_grapheme_count;
+        self.on_text_replaced(&self.cursor_index.clone(), &self.cursor_index.clone(), &Pos { x: self.cursor_index.x + inserted_grapheme_count, y: self.cursor_index.y }, text_to_insert);
+        self.set_cursor_x(self.cursor_index.x + inserted_grapheme_count);
+    }
+
+    fn delete_selected_text(&mut self, ui: &Ui) {
+        if self.has_column_selection() {
+            self.edit_column_selection(ui, ColumnEdit::Insert(""));
+        } else if self.has_selection() {
+            let selection_start_index = self.selection_start_index.clone().unwrap();
+            let selection_end_index = self.selection_end_index.clone().unwrap();
+            if self.replace_text(ui, &selection_start_index, &selection_end_index, "").is_none() {
+                return;
+            }
+            self.reset_selection();
+            self.set_cursor_y(selection_start_index.y);
+            self.set_cursor_x(selection_start_index.x);
+        }
+    }
+
 
     fn first_line_index(&self) -> usize {
         let mut first_line_index = (self.scroll_offset.y / self.line_height) as usize;
@@ -526,8 +1018,23 @@ impl TextEditor {
     }
 
     fn on_key_press(&mut self, ui: &Ui, key: Key, modifiers: &Modifiers) {
+        if self.has_column_selection() {
+            match key {
+                Key::Backspace => return self.edit_column_selection(ui, ColumnEdit::Backspace),
+                Key::Delete => return self.edit_column_selection(ui, ColumnEdit::Delete),
+                _ => {}
+            }
+        }
         match key {
             Key::ArrowDown | Key::ArrowUp => {
+                if modifiers.alt && modifiers.shift {
+                    self.move_column_selection_head(0, if key == Key::ArrowDown { 1 } else { -1 });
+                    return;
+                }
+                if modifiers.alt {
+                    self.move_lines(ui, key == Key::ArrowUp);
+                    return;
+                }
                 if modifiers.shift {
                     if self.start_dragged_index.is_none() {
                         self.start_dragged_index = Some(self.cursor_index.clone());
@@ -547,6 +1054,10 @@ impl TextEditor {
                 }
             }
             Key::ArrowLeft | Key::ArrowRight => {
+                if modifiers.alt && modifiers.shift {
+                    self.move_column_selection_head(if key == Key::ArrowRight { 1 } else { -1 }, 0);
+                    return;
+                }
                 if modifiers.shift {
                     if self.start_dragged_index.is_none() {
                         self.start_dragged_index = Some(self.cursor_index.clone());
@@ -567,20 +1078,30 @@ impl TextEditor {
             }
             Key::Backspace => {
                 let line = &self.lines[self.cursor_index.y];
-                let line_len = line.len();
+                let line_len = line.grapheme_count();
                 if self.has_selection() {
                     self.key_press_on_selection(None);
                     return;
                 } else if line_len > 0 && self.cursor_index.x > 0 {
+                    let start_index = Pos { x: self.cursor_index.x - 1, y: self.cursor_index.y };
+                    if !self.is_edit_allowed(&start_index, &self.cursor_index) {
+                        return;
+                    }
                     self.push_action_to_unsaved_state(ui, SingleAction::RemoveChar(RemoveCharAction {
                         start_pos: self.cursor_index.clone(),
-                        char: self.lines[self.cursor_index.y].chars().nth(self.cursor_index.x - 1).unwrap(),
+                        char: self.lines[self.cursor_index.y].grapheme_at(self.cursor_index.x - 1).unwrap().to_string(),
                     }));
+                    self.on_text_replaced(&start_index, &self.cursor_index.clone(), &start_index, "");
                     self.set_cursor_x(self.cursor_index.x - 1);
                     self.remove_char_at(self.cursor_index.clone());
                 } else if self.cursor_index.x == 0 && self.cursor_index.y > 0 {
+                    let previous_line_len = self.lines[self.cursor_index.y - 1].grapheme_count();
+                    let start_index = Pos { x: previous_line_len, y: self.cursor_index.y - 1 };
+                    if !self.is_edit_allowed(&start_index, &self.cursor_index) {
+                        return;
+                    }
                     self.push_action_to_unsaved_state(ui, SingleAction::RemoveLine(self.cursor_index.y));
-                    let previous_line_len = self.lines[self.cursor_index.y - 1].len();
+                    self.on_text_replaced(&start_index, &self.cursor_index.clone(), &start_index, "");
                     let line = self.lines.remove(self.cursor_index.y);
                     if !line.is_empty() {
                         self.lines[self.cursor_index.y - 1].push_str(line.as_str());
@@ -592,22 +1113,37 @@ impl TextEditor {
             Key::Delete => {
                 let line = &self.lines[self.cursor_index.y];
                 let line_len = line.len();
-                let x_index = line.byte_index_from_char_index(self.cursor_index.x);
+                let x_index = line.byte_index_from_grapheme_index(self.cursor_index.x);
                 if self.has_selection() {
                     self.key_press_on_selection(None);
                     return;
                 } else if line_len > x_index {
+                    let end_index = Pos { x: self.cursor_index.x + 1, y: self.cursor_index.y };
+                    if !self.is_edit_allowed(&self.cursor_index, &end_index) {
+                        return;
+                    }
                     self.push_action_to_unsaved_state(ui, SingleAction::RemoveChar(RemoveCharAction {
                         start_pos: self.cursor_index.clone(),
-                        char: self.lines[self.cursor_index.y].chars().nth(self.cursor_index.x).unwrap(),
+                        char: self.lines[self.cursor_index.y].grapheme_at(self.cursor_index.x).unwrap().to_string(),
                     }));
+                    self.on_text_replaced(&self.cursor_index.clone(), &end_index, &self.cursor_index.clone(), "");
                     self.remove_char_at(self.cursor_index.clone());
                 } else if line_len == 0 && self.cursor_index.y + 1 < self.lines.len() {
+                    let end_index = Pos { x: 0, y: self.cursor_index.y + 1 };
+                    if !self.is_edit_allowed(&self.cursor_index, &end_index) {
+                        return;
+                    }
                     self.push_action_to_unsaved_state(ui, SingleAction::RemoveLine(self.cursor_index.y));
+                    self.on_text_replaced(&self.cursor_index.clone(), &end_index, &self.cursor_index.clone(), "");
                     self.lines.remove(self.cursor_index.y);
                     self.set_cursor_y(self.cursor_index.y);
                 } else if line_len == x_index && self.cursor_index.y + 1 < self.lines.len() {
+                    let end_index = Pos { x: 0, y: self.cursor_index.y + 1 };
+                    if !self.is_edit_allowed(&self.cursor_index, &end_index) {
+                        return;
+                    }
                     self.push_action_to_unsaved_state(ui, SingleAction::RemoveLine(self.cursor_index.y + 1));
+                    self.on_text_replaced(&self.cursor_index.clone(), &end_index, &self.cursor_index.clone(), "");
                     let mut line = self.lines.remove(self.cursor_index.y + 1);
                     if !line.is_empty() {
                         self.lines[self.cursor_index.y].push_str(line.as_str());
@@ -615,14 +1151,27 @@ impl TextEditor {
                 }
             }
             Key::Enter => {
+                if self.has_column_selection() {
+                    // the line break goes at the cursor only, the block is removed first when it has a width
+                    let (top_left, bottom_right) = self.column_selection_bounds();
+                    if top_left.x != bottom_right.x {
+                        self.edit_column_selection(ui, ColumnEdit::Insert(""));
+                    }
+                    self.column_selection_anchor = None;
+                    self.column_selection_head = None;
+                }
                 if self.has_selection() {
                     self.key_press_on_selection(None);
                     return;
                 }
+                if !self.is_edit_allowed(&self.cursor_index, &self.cursor_index) {
+                    return;
+                }
                 self.has_pressed_arrow_key = true;
+                self.on_text_replaced(&self.cursor_index.clone(), &self.cursor_index.clone(), &Pos { x: 0, y: self.cursor_index.y + 1 }, "\n");
                 let line = &self.lines[self.cursor_index.y].clone();
                 let line_len = line.len();
-                let x_index = line.byte_index_from_char_index(self.cursor_index.x);
+                let x_index = line.byte_index_from_grapheme_index(self.cursor_index.x);
                 let line_start = &line[0..x_index];
                 let line_end = &line[x_index..line_len];
                 self.lines[self.cursor_index.y] = line_start.to_string();
@@ -636,10 +1185,54 @@ impl TextEditor {
                     let y_index = self.lines.len() - 1;
                     let last_line = &self.lines[y_index];
                     self.start_dragged_index = Some(Pos { x: 0, y: 0 });
-                    self.stop_dragged_index = Some(Pos { x: last_line.chars().count(), y: y_index });
+                    self.stop_dragged_index = Some(Pos { x: last_line.grapheme_count(), y: y_index });
                     self.set_selection();
                 }
             }
+            Key::D => {
+                if modifiers.ctrl && modifiers.shift { // TODO check for mac
+                    self.duplicate_lines(ui);
+                }
+            }
+            Key::K => {
+                if modifiers.ctrl && modifiers.shift { // TODO check for mac
+                    self.delete_lines(ui);
+                }
+            }
+            Key::U => {
+                if modifiers.ctrl && modifiers.shift { // TODO check for mac
+                    self.toggle_case(ui);
+                }
+            }
+            Key::J => {
+                if modifiers.ctrl { // TODO check for mac
+                    self.join_lines(ui);
+                }
+            }
+            Key::Slash | Key::Questionmark => {
+                if modifiers.ctrl { // TODO check for mac
+                    if modifiers.shift {
+                        self.toggle_block_comment(ui);
+                    } else {
+                        self.toggle_line_comment(ui);
+                    }
+                }
+            }
+            Key::Plus | Key::Equals => {
+                if modifiers.ctrl { // TODO check for mac
+                    self.zoom_in();
+                }
+            }
+            Key::Minus => {
+                if modifiers.ctrl { // TODO check for mac
+                    self.zoom_out();
+                }
+            }
+            Key::Num0 => {
+                if modifiers.ctrl { // TODO check for mac
+                    self.set_zoom(1.0);
+                }
+            }
             Key::S => {
                 if modifiers.ctrl { // TODO check for mac
                     println!("ctr + s");
@@ -648,9 +1241,25 @@ impl TextEditor {
             Key::Z => {
                 if modifiers.ctrl { // TODO check for mac
                     println!("ctr + z");
+                    if let Some(state) = self.history.last() {
+                        let is_allowed = match &state.bulk_action {
+                            BulkAction::AddText(action) | BulkAction::RemoveText(action) =>
+                                self.is_lines_replacement_allowed(action.start_index, action.end_index, &action.lines),
+                        };
+                        if !is_allowed {
+                            return;
+                        }
+                    }
                     let maybe_state = self.history.pop();
+                    if self.saved_history_len.map_or(false, |saved_history_len| self.history.len() < saved_history_len) {
+                        self.saved_history_len = None;
+                    }
                     if maybe_state.is_some() {
                         let state = maybe_state.unwrap();
+                        match &state.bulk_action {
+                            BulkAction::AddText(action) | BulkAction::RemoveText(action) =>
+                                self.on_lines_replaced(action.start_index, action.end_index.min(self.lines.len() - 1), &action.lines),
+                        }
                         match state.bulk_action {
                             BulkAction::AddText(action) => {
                                 self.lines.splice(action.start_index..self.lines.len().min(action.end_index + 1), action.lines);
@@ -681,11 +1290,93 @@ impl TextEditor {
     }
 
     fn remove_char_at(&mut self, pos: Pos<usize>) {
-        self.lines[pos.y].delete_char_range(pos.x..pos.x + 1)
+        let line = &mut self.lines[pos.y];
+        let start_x_index = line.byte_index_from_grapheme_index(pos.x);
+        let end_x_index = line.byte_index_from_grapheme_index(pos.x + 1);
+        line.replace_range(start_x_index..end_x_index, "");
     }
 
-    fn insert_text_at(&mut self, text_to_insert: &String, pos: Pos<usize>) {
-        self.lines[pos.y].insert_text(text_to_insert, pos.x);
+    fn insert_text_at(&mut self, text_to_insert: &str, pos: Pos<usize>) {
+        let line = &mut self.lines[pos.y];
+        let x_index = line.byte_index_from_grapheme_index(pos.x);
+        line.insert_str(x_index, text_to_insert);
+    }
+
+    // Replace text between start_index and end_index with text, recorded as a single history entry.
+    // Returns the position at the end of the inserted text, None when the edit is not allowed.
+    fn replace_text(&mut self, ui: &Ui, start_index: &Pos<usize>, end_index: &Pos<usize>, text: &str) -> Option<Pos<usize>> {
+        if !self.is_edit_allowed(start_index, end_index) {
+            return None;
+        }
+        let start_line = &self.lines[start_index.y];
+        let start_x_index = start_line.byte_index_from_grapheme_index(start_index.x);
+        let end_line = &self.lines[end_index.y];
+        let end_x_index = end_line.byte_index_from_grapheme_index(end_index.x);
+        let mut new_lines = text.split('\n').map(|line| line.to_string()).collect::<Vec<String>>();
+        let last = new_lines.len() - 1;
+        let new_end_index = if last == 0 {
+            Pos { x: start_index.x + new_lines[0].grapheme_count(), y: start_index.y }
+        } else {
+            Pos { x: new_lines[last].grapheme_count(), y: start_index.y + last }
+        };
+        new_lines[0].insert_str(0, &start_line[0..start_x_index]);
+        new_lines[last].push_str(&end_line[end_x_index..]);
+        self.on_text_replaced(start_index, end_index, &new_end_index, text);
+        self.splice_lines(ui, start_index.y, end_index.y, new_lines);
+        Some(new_end_index)
+    }
+
+    // Replace lines from start_index to end_index (inclusive) with new_lines, recorded as a single history entry.
+    // Returns false when the edit is not allowed.
+    fn replace_lines(&mut self, ui: &Ui, start_index: usize, end_index: usize, new_lines: Vec<String>) -> bool {
+        let end_index = end_index.min(self.lines.len() - 1);
+        if !self.is_lines_replacement_allowed(start_index, end_index, &new_lines) {
+            return false;
+        }
+        self.on_lines_replaced(start_index, end_index, &new_lines);
+        self.splice_lines(ui, start_index, end_index, new_lines);
+        true
+    }
+
+    // Replace lines as replace_lines does, without checking read only and protected ranges.
+    pub(crate) fn splice_lines(&mut self, ui: &Ui, start_index: usize, end_index: usize, new_lines: Vec<String>) {
+        self.commit_unsaved_state();
+        let mut start_index = start_index;
+        let mut end_index = end_index.min(self.lines.len() - 1);
+        let mut new_lines = new_lines;
+        if new_lines.is_empty() {
+            // history actions refer to an inclusive line range, keep at least one surrounding line in it.
+            if end_index + 1 < self.lines.len() {
+                end_index += 1;
+                new_lines.push(self.lines[end_index].clone());
+            } else if start_index > 0 {
+                start_index -= 1;
+                new_lines.insert(0, self.lines[start_index].clone());
+            }
+        }
+        let previous_lines = self.lines[start_index..=end_index].to_vec();
+        let new_end_index = start_index + new_lines.len().max(1) - 1;
+        let is_addition = new_lines.len() >= previous_lines.len();
+        self.lines.splice(start_index..=end_index, new_lines);
+        if self.lines.is_empty() {
+            self.lines.push(String::default());
+        }
+        self.lines_count = self.lines.len();
+        let text_action = TextAction {
+            start_index,
+            end_index: new_end_index,
+            lines: previous_lines,
+        };
+        self.history.push(State {
+            created_at: ui.input(|input| input.time),
+            cursor_index: self.cursor_index.clone(),
+            cursor_pos: self.cursor_pos.clone(),
+            bulk_action: if is_addition {
+                BulkAction::AddText(text_action)
+            } else {
+                BulkAction::RemoveText(text_action)
+            },
+        });
     }
 
     fn after_cursor_position_change(&mut self) {
@@ -696,7 +1387,7 @@ impl TextEditor {
             *self.closing_char_index.borrow_mut() = None;
             return;
         }
-        let maybe_char = self.lines[self.cursor_index.y].chars().nth(self.cursor_index.x - 1);
+        let maybe_char = self.lines[self.cursor_index.y].grapheme_at(self.cursor_index.x - 1).and_then(single_char);
         if maybe_char.is_some() {
             if maybe_char.unwrap() == '{' || maybe_char.unwrap() == '(' || maybe_char.unwrap() == '[' {
                 let mut index = self.cursor_index.clone();
@@ -728,7 +1419,7 @@ impl TextEditor {
             self.set_cursor_y(self.lines_count - 1);
         }
         let line = &self.lines[self.cursor_index.y];
-        let line_len = line.len();
+        let line_len = line.grapheme_count();
         if self.cursor_index.x > line_len {
             self.set_cursor_x(line_len);
         }
@@ -750,10 +1441,23 @@ impl TextEditor {
     }
 
     #[inline]
-    fn x_to_index(&self, x: f32) -> usize {
+    fn x_to_column(&self, x: f32) -> usize {
         ((x) / self.char_width) as usize
     }
 
+    #[inline]
+    fn pointer_to_index(&self, pointer: Pos2) -> Pos<usize> {
+        let y = self.y_to_index(pointer.y - self.text_editor_viewport.min.y).min(self.lines.len() - 1);
+        let column = self.x_to_column(pointer.x - self.line_x_offset());
+        Pos { x: self.lines[y].grapheme_index_from_column(column), y }
+    }
+
+    #[inline]
+    fn pointer_to_column(&self, pointer: Pos2) -> Pos<usize> {
+        let y = self.y_to_index(pointer.y - self.text_editor_viewport.min.y).min(self.lines.len() - 1);
+        Pos { x: self.x_to_column(pointer.x - self.line_x_offset()), y }
+    }
+
     #[inline]
     fn line_at(&self, y: f32) -> &str {
         self.lines[self.y_to_index(y)].as_str()
@@ -762,7 +1466,7 @@ impl TextEditor {
     #[inline]
     fn index_to_pos(&self, index: Pos<usize>) -> Pos<f32> {
         Pos::<f32> {
-            x: self.index_to_x(index.x),
+            x: self.index_to_x(&index),
             y: self.index_to_y(index.y),
         }
     }
@@ -779,8 +1483,23 @@ impl TextEditor {
     }
 
     #[inline]
-    fn index_to_x(&self, index: usize) -> f32 {
-        index as f32 * self.char_width + (self.line_x_offset())
+    fn column_to_x(&self, column: usize) -> f32 {
+        column as f32 * self.char_width + (self.line_x_offset())
+    }
+
+    #[inline]
+    fn index_to_x(&self, index: &Pos<usize>) -> f32 {
+        if index.y >= self.lines.len() {
+            return self.column_to_x(index.x);
+        }
+        self.column_to_x(self.lines[index.y].column_from_grapheme_index(index.x))
+    }
+
+    // Width of the grapheme at index, wide characters take 2 columns.
+    #[inline]
+    fn grapheme_width_at(&self, index: &Pos<usize>) -> f32 {
+        let width = self.lines.get(index.y).and_then(|line| line.grapheme_at(index.x)).map_or(1, grapheme_width);
+        width as f32 * self.char_width
     }
 
     #[inline]
@@ -791,6 +1510,8 @@ impl TextEditor {
         self.cursor_index.y = new_value;
         self.cursor_pos.y = self.index_to_y(self.cursor_index.y);
         self.sanitize_cursor_position();
+        // same index on another line can be at another column
+        self.cursor_pos.x = self.index_to_x(&self.cursor_index);
         self.after_cursor_position_change();
     }
 
@@ -800,7 +1521,7 @@ impl TextEditor {
             return;
         }
         self.cursor_index.x = new_value;
-        self.cursor_pos.x = self.index_to_x(self.cursor_index.x);
+        self.cursor_pos.x = self.index_to_x(&self.cursor_index);
         self.sanitize_cursor_position();
         self.after_cursor_position_change();
     }
@@ -854,8 +1575,8 @@ impl TextEditor {
         for (start_pos, end_pos) in self.word_occurrences.borrow().iter() {
             shapes.push(epaint::Shape::Rect(RectShape {
                 rect: Rect {
-                    min: Pos2 { x: self.index_to_x(start_pos.x) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(start_pos.y, first_line_index) },
-                    max: Pos2 { x: self.index_to_x(end_pos.x) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(start_pos.y, first_line_index) + self.line_height },
+                    min: Pos2 { x: self.index_to_x(start_pos) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(start_pos.y, first_line_index) },
+                    max: Pos2 { x: self.index_to_x(end_pos) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(start_pos.y, first_line_index) + self.line_height },
                 },
                 rounding: Rounding::ZERO,
                 fill: Color32::YELLOW,
@@ -873,8 +1594,8 @@ impl TextEditor {
             if opening_char_index.y >= first_line_index {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
-                        min: Pos2 { x: self.index_to_x(opening_char_index.x) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(opening_char_index.y, first_line_index) },
-                        max: Pos2 { x: self.index_to_x(opening_char_index.x) as f32 + self.char_width, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(opening_char_index.y, first_line_index) + self.line_height },
+                        min: Pos2 { x: self.index_to_x(opening_char_index) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(opening_char_index.y, first_line_index) },
+                        max: Pos2 { x: self.index_to_x(opening_char_index) as f32 + self.char_width, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(opening_char_index.y, first_line_index) + self.line_height },
                     },
                     rounding: Rounding::ZERO,
                     fill: Color32::GREEN,
@@ -890,8 +1611,8 @@ impl TextEditor {
             if closing_char_index.y >= first_line_index && closing_char_index.x > 0 {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
-                        min: Pos2 { x: self.index_to_x(closing_char_index.x - 1) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(closing_char_index.y, first_line_index) },
-                        max: Pos2 { x: self.index_to_x(closing_char_index.x - 1) as f32 + self.char_width, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(closing_char_index.y, first_line_index) + self.line_height },
+                        min: Pos2 { x: self.index_to_x(&Pos { x: closing_char_index.x - 1, y: closing_char_index.y }) as f32, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(closing_char_index.y, first_line_index) },
+                        max: Pos2 { x: self.index_to_x(&Pos { x: closing_char_index.x - 1, y: closing_char_index.y }) as f32 + self.char_width, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(closing_char_index.y, first_line_index) + self.line_height },
                     },
                     rounding: Rounding::ZERO,
                     fill: Color32::GREEN,
@@ -921,7 +1642,7 @@ impl TextEditor {
 
     fn paint_debug_char(&self, top: f32, mut shapes: &mut Vec<Shape>, i: usize, line_number: usize, frag: &String) {
         if line_number == self.cursor_index.y {
-            for j in 0..frag.len() {
+            for j in 0..frag.columns_count() {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: emath::Rect {
                         min: Pos2 { x: self.text_editor_viewport.min.x + j as f32 * self.char_width, y: top + (self.line_height) * (i) as f32 },
@@ -979,7 +1700,15 @@ impl TextEditor {
 
     fn gutter(&mut self, ui: &mut Ui, gutter_rect: Rect, first_line_index: usize, last_line_index: usize) {
         let mut brush_mut = self.glyph_brush_line_number.as_ref().lock().unwrap();
-        let numbers = (first_line_index..last_line_index).map(|line_number| (line_number, format!("{}\n", line_number + 1))).collect::<Vec<(usize, String)>>();
+        let numbers = (first_line_index..last_line_index).enumerate()
+            .map(|(row, line_number)| self.sticky_lines.get(row).copied().unwrap_or(line_number))
+            .map(|line_number| {
+                let displayed_number = match self.gutter_line_numbers.as_ref() {
+                    Some(numbers) => numbers.get(line_number).copied().flatten(),
+                    None => Some(line_number),
+                };
+                (line_number, displayed_number.map_or_else(|| "\n".to_string(), |number| format!("{}\n", number + 1)))
+            }).collect::<Vec<(usize, String)>>();
         brush_mut.queue(glow_glyph::Section {
             screen_position: (0.0, 0.0),
             text: numbers.iter().map(|(line_number, text)| {
@@ -1002,6 +1731,7 @@ impl TextEditor {
                 fill_texture_id: Default::default(),
                 uv: Rect::ZERO,
             }));
+            ui.painter().extend(self.git_marker_shapes(gutter_rect, first_line_index, last_line_index));
             let glyph_brush = self.glyph_brush_line_number.clone();
             ui.painter().add(egui::epaint::PaintCallback {
                 rect: gutter_rect,
@@ -1023,13 +1753,18 @@ trait Selection {
     fn is_single_line_selection(&self) -> bool;
     fn is_two_lines_selection(&self) -> bool;
     fn selection_shapes(&self, first_line_index: usize) -> Vec<Shape>;
-    fn key_press_on_selection(&mut self, text_to_insert: Option<&str>);
+    fn key_press_on_selection(&mut self, text_to_insert: Option<&str>) -> bool;
+    fn select_range(&mut self, start_index: Pos<usize>, end_index: Pos<usize>);
+    fn selected_line_range(&self) -> (usize, usize);
+    fn selected_text(&self) -> String;
 }
 
 impl Selection for TextEditor {
     fn reset_selection(&mut self) {
         self.selection_start_index = None;
         self.selection_end_index = None;
+        self.column_selection_anchor = None;
+        self.column_selection_head = None;
         self.start_dragged_index = None;
         self.stop_dragged_index = None;
         self.highlighted_word = None;
@@ -1055,11 +1790,11 @@ impl Selection for TextEditor {
         if end_index.y >= self.lines_count {
             end_index.y = self.lines_count - 1;
         }
-        let line_len = self.lines[start_index.y].len();
+        let line_len = self.lines[start_index.y].grapheme_count();
         if start_index.x > line_len {
             start_index.x = line_len;
         }
-        let line_len = self.lines[end_index.y].len();
+        let line_len = self.lines[end_index.y].grapheme_count();
         if end_index.x > line_len {
             end_index.x = line_len;
         }
@@ -1086,6 +1821,9 @@ impl Selection for TextEditor {
     }
 
     fn selection_shapes(&self, first_line_index: usize) -> Vec<Shape> {
+        if self.has_column_selection() {
+            return self.column_selection_shapes(first_line_index);
+        }
         if !self.has_selection() {
             return vec![];
         }
@@ -1096,8 +1834,8 @@ impl Selection for TextEditor {
             vec![
                 Shape::Rect(RectShape {
                     rect: Rect {
-                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
-                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) + self.line_height },
+                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
+                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) + self.line_height },
                     },
                     fill: Color32::LIGHT_BLUE,
                     rounding: Rounding::ZERO,
@@ -1111,7 +1849,7 @@ impl Selection for TextEditor {
             if self.selection_start_index.as_ref().unwrap().y >= first_line_index {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
-                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
+                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
                         max: Pos2 { x: self.text_editor_viewport.max.x, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) + self.line_height },
                     },
                     fill: Color32::LIGHT_BLUE,
@@ -1125,7 +1863,7 @@ impl Selection for TextEditor {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
                         min: Pos2 { x: self.text_editor_viewport.min.x, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) },
-                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) + self.line_height },
+                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) + self.line_height },
                     },
                     rounding: Rounding::ZERO,
                     fill: Color32::LIGHT_BLUE,
@@ -1140,7 +1878,7 @@ impl Selection for TextEditor {
             if self.selection_start_index.as_ref().unwrap().y >= first_line_index {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
-                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
+                        min: Pos2 { x: self.index_to_x(self.selection_start_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) },
                         max: Pos2 { x: self.text_editor_viewport.max.x, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_start_index.as_ref().unwrap().y, first_line_index) + self.line_height },
                     },
                     rounding: Rounding::ZERO,
@@ -1166,7 +1904,7 @@ impl Selection for TextEditor {
                 shapes.push(epaint::Shape::Rect(RectShape {
                     rect: Rect {
                         min: Pos2 { x: self.text_editor_viewport.min.x, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) },
-                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap().x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) + self.line_height },
+                        max: Pos2 { x: self.index_to_x(self.selection_end_index.as_ref().unwrap()), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.selection_end_index.as_ref().unwrap().y, first_line_index) + self.line_height },
                     },
                     rounding: Rounding::ZERO,
                     fill: Color32::LIGHT_BLUE,
@@ -1179,36 +1917,42 @@ impl Selection for TextEditor {
         }
     }
 
-    fn key_press_on_selection(&mut self, text_to_insert: Option<&str>) {
+    // Returns false when the edit is not allowed.
+    fn key_press_on_selection(&mut self, text_to_insert: Option<&str>) -> bool {
         let selection_start_index = self.selection_start_index.as_ref().unwrap().clone();
         let selection_end_index = self.selection_end_index.as_ref().unwrap().clone();
+        if !self.is_edit_allowed(&selection_start_index, &selection_end_index) {
+            return false;
+        }
+        let text_end_index = Pos { x: selection_start_index.x + text_to_insert.map_or(0, |text| text.grapheme_count()), y: selection_start_index.y };
+        self.on_text_replaced(&selection_start_index, &selection_end_index, &text_end_index, text_to_insert.unwrap_or(""));
         if self.is_single_line_selection() {
             let line = &self.lines[selection_start_index.y];
             let line_len = line.len();
-            let start_x_index = line.byte_index_from_char_index(selection_start_index.x);
-            let end_x_index = line.byte_index_from_char_index(selection_end_index.x);
+            let start_x_index = line.byte_index_from_grapheme_index(selection_start_index.x);
+            let end_x_index = line.byte_index_from_grapheme_index(selection_end_index.x);
             self.lines[selection_start_index.y] = format!("{}{}{}", &line[0..start_x_index],
                                                           text_to_insert.unwrap_or(""),
                                                           &line[end_x_index..line_len]);
         } else if self.is_two_lines_selection() {
             let line = &self.lines[selection_start_index.y];
-            let start_x_index = line.byte_index_from_char_index(selection_start_index.x);
+            let start_x_index = line.byte_index_from_grapheme_index(selection_start_index.x);
             let new_line_start = String::from(&line[0..start_x_index]);
             self.lines.remove(selection_start_index.y);
 
             let line = &self.lines[selection_start_index.y];
             let line_len = line.len();
-            let end_x_index = line.byte_index_from_char_index(selection_end_index.x);
+            let end_x_index = line.byte_index_from_grapheme_index(selection_end_index.x);
             let new_line_end = String::from(&line[end_x_index..line_len]);
             self.lines[selection_start_index.y] = format!("{}{}{}", new_line_start, text_to_insert.unwrap_or(""), new_line_end);
         } else {
             let line = &self.lines[selection_start_index.y];
-            let start_x_index = line.byte_index_from_char_index(selection_start_index.x);
+            let start_x_index = line.byte_index_from_grapheme_index(selection_start_index.x);
             let new_line_start = String::from(&line[0..start_x_index]);
 
             let line = &self.lines[selection_end_index.y];
             let line_len = line.len();
-            let end_x_index = line.byte_index_from_char_index(selection_end_index.x);
+            let end_x_index = line.byte_index_from_grapheme_index(selection_end_index.x);
             let new_line_end = String::from(&line[end_x_index..line_len]);
 
             let text_start = &self.lines[0..selection_start_index.y];
@@ -1219,6 +1963,50 @@ impl Selection for TextEditor {
         self.set_cursor_y(selection_start_index.y);
         self.set_cursor_x(selection_start_index.x);
         self.reset_selection();
+        true
+    }
+
+    fn select_range(&mut self, start_index: Pos<usize>, end_index: Pos<usize>) {
+        self.reset_selection();
+        self.start_dragged_index = Some(start_index);
+        self.stop_dragged_index = Some(end_index);
+        self.set_selection();
+    }
+
+    fn selected_line_range(&self) -> (usize, usize) {
+        if !self.has_selection() {
+            return (self.cursor_index.y, self.cursor_index.y);
+        }
+        let start_y = self.selection_start_index.as_ref().unwrap().y;
+        let mut end_y = self.selection_end_index.as_ref().unwrap().y;
+        // a selection ending at the beginning of a line does not touch that line
+        if end_y > start_y && self.selection_end_index.as_ref().unwrap().x == 0 {
+            end_y -= 1;
+        }
+        (start_y, end_y)
+    }
+
+    fn selected_text(&self) -> String {
+        if !self.has_selection() {
+            return String::default();
+        }
+        let selection_start_index = self.selection_start_index.as_ref().unwrap();
+        let selection_end_index = self.selection_end_index.as_ref().unwrap();
+        let start_line = &self.lines[selection_start_index.y];
+        let start_x_index = start_line.byte_index_from_grapheme_index(selection_start_index.x);
+        let end_line = &self.lines[selection_end_index.y];
+        let end_x_index = end_line.byte_index_from_grapheme_index(selection_end_index.x);
+        if self.is_single_line_selection() {
+            return start_line[start_x_index..end_x_index].to_string();
+        }
+        let mut text = start_line[start_x_index..].to_string();
+        for line in self.lines[selection_start_index.y + 1..selection_end_index.y].iter() {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&end_line[0..end_x_index]);
+        text
     }
 }
 
@@ -1226,9 +2014,44 @@ trait HasUnsavedState {
     fn init_unsaved_state(&mut self, time: f64);
     fn push_action_to_unsaved_state(&mut self, ui: &Ui, action: SingleAction);
     fn flush_unsaved_state(&mut self, time: f64) -> Option<State>;
+    fn commit_unsaved_state(&mut self);
+}
+
+// Grapheme ranges of the highlighted word occurrences in a line, the word has to follow a non word grapheme.
+fn word_occurrences_in_line(highlighted_word: &[&str], frag: &str) -> Vec<(usize, usize)> {
+    let mut occurrences = vec![];
+    let mut should_search_word = false;
+    let mut word_char_search_index = 0;
+    let mut start_index = 0;
+    for (i, grapheme) in frag.graphemes(true).enumerate() {
+        if TextEditor::is_grapheme_non_part_of_word(grapheme) {
+            if word_char_search_index == highlighted_word.len() {
+                occurrences.push((start_index + 1, i));
+            }
+            should_search_word = true;
+            word_char_search_index = 0;
+            start_index = i;
+            continue;
+        }
+        if should_search_word {
+            let word_char = highlighted_word.get(word_char_search_index);
+            if word_char.is_some() && *word_char.unwrap() == grapheme {
+                word_char_search_index += 1;
+            } else {
+                word_char_search_index = 0;
+                should_search_word = false;
+            }
+        }
+    }
+    if word_char_search_index == highlighted_word.len() {
+        occurrences.push((start_index + 1, frag.grapheme_count()));
+    }
+    occurrences
 }
 
 const InactivityPeriod: f64 = 2.0;
+const TextColor: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const DoubleClickDelay: f64 = 0.3;
 
 impl HasUnsavedState for TextEditor {
     fn init_unsaved_state(&mut self, time: f64) {
@@ -1296,10 +2119,14 @@ impl HasUnsavedState for TextEditor {
             let action = unsaved_state.actions.pop().unwrap();
             match action {
                 SingleAction::AddChar(action) => {
-                    lines[action.start_pos.y - min_index].delete_char_range(action.start_pos.x..action.start_pos.x + 1);
+                    let line = &mut lines[action.start_pos.y - min_index];
+                    let x_index = line.byte_index_from_grapheme_index(action.start_pos.x);
+                    line.replace_range(x_index..(x_index + action.char.len()).min(line.len()), "");
                 }
                 SingleAction::RemoveChar(action) => {
-                    lines[action.start_pos.y - min_index].insert((action.start_pos.x.max(1)) - 1, action.char);
+                    let line = &mut lines[action.start_pos.y - min_index];
+                    let x_index = line.byte_index_from_grapheme_index((action.start_pos.x.max(1)) - 1);
+                    line.insert_str(x_index, action.char.as_str());
                 }
                 SingleAction::RemoveLine(line_index) => {
                     lines.insert(line_index - min_index, String::default());
@@ -1331,4 +2158,16 @@ impl HasUnsavedState for TextEditor {
             },
         })
     }
-}
\ No newline at end of file
+
+    fn commit_unsaved_state(&mut self) {
+        // Bulk edits are recorded directly into history, pending single actions have to be saved before.
+        if self.unsaved_stated.is_none() {
+            return;
+        }
+        let time = self.unsaved_stated.as_ref().unwrap().last_activity_at + InactivityPeriod;
+        let maybe_state = self.flush_unsaved_state(time);
+        if maybe_state.is_some() {
+            self.history.push(maybe_state.unwrap());
+        }
+    }
+}
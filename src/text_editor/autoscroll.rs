@@ -0,0 +1,39 @@
+use eframe::egui::Ui;
+use crate::text_editor::{Pos, TextEditor};
+
+// Scroll speed in pixels per second, when pointer has just left the viewport.
+const AutoScrollMinSpeed: f32 = 200.0;
+// Additional pixels per second, for each pixel the pointer is away from the viewport.
+const AutoScrollSpeedFactor: f32 = 12.0;
+
+// While drag-selecting with the pointer outside the viewport, the view scrolls toward the pointer.
+pub(crate) trait AutoScroll {
+    fn autoscroll_delta(&mut self, ui: &Ui) -> Option<Pos<f32>>;
+}
+
+impl AutoScroll for TextEditor {
+    fn autoscroll_delta(&mut self, ui: &Ui) -> Option<Pos<f32>> {
+        let (time, is_pointer_down, maybe_pointer) = ui.input(|input| (input.time, input.pointer.primary_down(), input.pointer.interact_pos()));
+        if !self.is_mouse_selecting || !is_pointer_down || maybe_pointer.is_none() {
+            self.autoscroll_last_time = None;
+            return None;
+        }
+        let pointer = maybe_pointer.unwrap();
+        let viewport = self.text_editor_viewport;
+        let distance = Pos {
+            x: if pointer.x < viewport.min.x { pointer.x - viewport.min.x } else if pointer.x > viewport.max.x { pointer.x - viewport.max.x } else { 0.0 },
+            y: if pointer.y < viewport.min.y { pointer.y - viewport.min.y } else if pointer.y > viewport.max.y { pointer.y - viewport.max.y } else { 0.0 },
+        };
+        if distance.x == 0.0 && distance.y == 0.0 {
+            self.autoscroll_last_time = None;
+            return None;
+        }
+        // Time based, so scroll speed does not depend on frame rate.
+        let elapsed = (time - self.autoscroll_last_time.unwrap_or(time)) as f32;
+        self.autoscroll_last_time = Some(time);
+        // Pointer may not move, we still need frames to keep scrolling.
+        ui.ctx().request_repaint();
+        let speed = |d: f32| if d == 0.0 { 0.0 } else { d.signum() * AutoScrollMinSpeed + d * AutoScrollSpeedFactor };
+        Some(Pos { x: speed(distance.x) * elapsed, y: speed(distance.y) * elapsed })
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+use eframe::egui::Ui;
+use crate::text_editor::TextEditor;
+use crate::text_editor::diff::diff_hunks;
+
+// Blame running in background is polled at this interval
+const BlamePollInterval: Duration = Duration::from_millis(100);
+// Spaces between the end of the cursor line and its blame
+pub(crate) const BlameGhostTextGap: &str = "    ";
+const UncommittedId: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone)]
+pub struct BlameCommit {
+    pub id: String,
+    pub author: String,
+    // Seconds since unix epoch
+    pub time: i64,
+    pub summary: String,
+}
+
+#[derive(Debug, Clone)]
+pub enum LineBlame {
+    Committed(Arc<BlameCommit>),
+    // Line changed in the working tree, or edited since blame was loaded
+    Uncommitted,
+}
+
+#[derive(Default)]
+pub(crate) struct Blame {
+    receiver: Option<Receiver<io::Result<Vec<LineBlame>>>>,
+    // Lines at the time blame was requested, blame refers to them
+    loaded_lines: Vec<String>,
+    lines_blame: Vec<LineBlame>,
+    // Index in loaded lines of each line, None when line has been edited since
+    line_origins: Vec<Option<usize>>,
+    edit_signature: Option<(usize, usize, usize)>,
+    // Error of the last blame, previous blame is kept
+    error: Option<io::Error>,
+}
+
+impl Blame {
+    pub(crate) fn invalidate(&mut self) {
+        self.edit_signature = None;
+    }
+}
+
+// Blame of each line of contents, as a version of the file (e.g: edited document), read with git from the file directory.
+pub fn read_blame(path: &Path, contents: &str) -> io::Result<Vec<LineBlame>> {
+    let directory = path.parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let mut child = Command::new("git").arg("-C").arg(directory).args(["blame", "--line-porcelain", "--contents", "-", "--"]).arg(file_name)
+        .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // contents are written while output is read, a full pipe would block both processes otherwise
+    let mut stdin = child.stdin.take().unwrap();
+    let contents = contents.to_string();
+    let writer = thread::spawn(move || stdin.write_all(contents.as_bytes()));
+    let output = child.wait_with_output()?;
+    // git exits without reading contents when the file is not tracked, its error is the relevant one
+    let write_result = writer.join().unwrap_or(Ok(()));
+    if !output.status.success() {
+        return Err(io::Error::new(io::ErrorKind::Other, String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    write_result?;
+    Ok(parse_line_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+// Each line is described by "<commit id> <original line> <final line>", commit headers, then the line content prefixed by a tab.
+fn parse_line_porcelain(output: &str) -> Vec<LineBlame> {
+    let mut commits: HashMap<String, Arc<BlameCommit>> = HashMap::new();
+    let mut lines_blame = vec![];
+    let mut commit = BlameCommit { id: String::new(), author: String::new(), time: 0, summary: String::new() };
+    let mut is_header_expected = true;
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            if commit.id == UncommittedId {
+                lines_blame.push(LineBlame::Uncommitted);
+            } else {
+                let commit = commits.entry(commit.id.clone()).or_insert_with(|| Arc::new(commit.clone()));
+                lines_blame.push(LineBlame::Committed(commit.clone()));
+            }
+            is_header_expected = true;
+        } else if is_header_expected {
+            commit.id = line.split(' ').next().unwrap_or_default().to_string();
+            is_header_expected = false;
+        } else if let Some(author) = line.strip_prefix("author ") {
+            commit.author = author.to_string();
+        } else if let Some(time) = line.strip_prefix("author-time ") {
+            commit.time = time.parse().unwrap_or_default();
+        } else if let Some(summary) = line.strip_prefix("summary ") {
+            commit.summary = summary.to_string();
+        }
+    }
+    lines_blame
+}
+
+// Index in loaded lines of each line, None when line has been edited since blame was loaded.
+fn line_origins(loaded_lines: &[String], lines: &[String]) -> Vec<Option<usize>> {
+    // Lines outside of hunks are unchanged since blame was loaded
+    let mut line_origins = Vec::with_capacity(lines.len());
+    let mut loaded_line_index = 0;
+    for hunk in diff_hunks(loaded_lines, lines) {
+        while line_origins.len() < hunk.right.start {
+            line_origins.push(Some(loaded_line_index));
+            loaded_line_index += 1;
+        }
+        line_origins.extend(hunk.right.map(|_| None));
+        loaded_line_index = hunk.left.end;
+    }
+    while line_origins.len() < lines.len() {
+        line_origins.push(Some(loaded_line_index));
+        loaded_line_index += 1;
+    }
+    line_origins
+}
+
+// "yyyy-mm-dd" from seconds since unix epoch, in UTC.
+fn format_date(time: i64) -> String {
+    // days to civil date, from Howard Hinnant's algorithms
+    let days = time.div_euclid(86400) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Author, date and summary of the commit which last changed the cursor line, displayed after its end.
+pub trait InlineBlame {
+    // Blame of the current lines, with path as their file, is computed in a background thread.
+    // Previous blame is kept until it completes.
+    fn load_blame(&mut self, path: &Path);
+    fn is_blame_loading(&self) -> bool;
+    fn line_blame(&self, line_index: usize) -> Option<LineBlame>;
+    // Why the last blame failed (e.g: file is not in a git repository)
+    fn blame_error(&self) -> Option<&io::Error>;
+}
+
+impl InlineBlame for TextEditor {
+    fn load_blame(&mut self, path: &Path) {
+        let (sender, receiver) = channel();
+        let path = path.to_path_buf();
+        // the document is blamed rather than the file, which may be older
+        let contents = self.text();
+        thread::spawn(move || {
+            let _ = sender.send(read_blame(&path, &contents));
+        });
+        self.blame.receiver = Some(receiver);
+        self.blame.loaded_lines = self.lines.clone();
+        self.blame.invalidate();
+    }
+
+    fn is_blame_loading(&self) -> bool {
+        self.blame.receiver.is_some()
+    }
+
+    fn line_blame(&self, line_index: usize) -> Option<LineBlame> {
+        if self.blame.lines_blame.is_empty() {
+            return None;
+        }
+        match self.blame.line_origins.get(line_index)? {
+            Some(loaded_line_index) => self.blame.lines_blame.get(*loaded_line_index).cloned(),
+            None => Some(LineBlame::Uncommitted),
+        }
+    }
+
+    fn blame_error(&self) -> Option<&io::Error> {
+        self.blame.error.as_ref()
+    }
+}
+
+impl TextEditor {
+    pub(crate) fn update_blame(&mut self, ui: &Ui) {
+        if let Some(receiver) = self.blame.receiver.as_ref() {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    match result {
+                        Ok(lines_blame) => {
+                            self.blame.lines_blame = lines_blame;
+                            self.blame.error = None;
+                        }
+                        Err(error) => self.blame.error = Some(error),
+                    }
+                    self.blame.receiver = None;
+                    self.blame.invalidate();
+                }
+                Err(TryRecvError::Empty) => ui.ctx().request_repaint_after(BlamePollInterval),
+                Err(TryRecvError::Disconnected) => self.blame.receiver = None,
+            }
+        }
+        if self.blame.lines_blame.is_empty() {
+            return;
+        }
+        let edit_signature = self.edit_signature();
+        if self.blame.edit_signature == Some(edit_signature) {
+            return;
+        }
+        self.blame.line_origins = line_origins(&self.blame.loaded_lines, &self.lines);
+        self.blame.edit_signature = Some(edit_signature);
+    }
+
+    // Text displayed after the cursor line
+    pub(crate) fn blame_ghost_text(&self) -> Option<String> {
+        if !self.options.inline_blame {
+            return None;
+        }
+        match self.line_blame(self.cursor_index.y)? {
+            LineBlame::Committed(commit) => Some(format!("{}, {} • {}", commit.author, format_date(commit.time), commit.summary)),
+            LineBlame::Uncommitted => Some("Uncommitted changes".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::text_editor::test_support::{git, lines, temp_repository};
+    use super::*;
+
+    fn commit_id(line_blame: &LineBlame) -> &str {
+        match line_blame {
+            LineBlame::Committed(commit) => &commit.id,
+            LineBlame::Uncommitted => UncommittedId,
+        }
+    }
+
+    #[test]
+    fn parse_line_porcelain_shares_repeated_commits() {
+        let output = "\
+1111111111111111111111111111111111111111 1 1 2
+author Alice
+author-time 86400
+summary First commit
+filename file.txt
+\tfirst line
+1111111111111111111111111111111111111111 2 2
+author Alice
+author-time 86400
+summary First commit
+filename file.txt
+\tsecond line
+0000000000000000000000000000000000000000 3 3 1
+author Not Committed Yet
+author-time 1700000000
+summary Version of file.txt from file.txt
+filename file.txt
+\tedited line
+2222222222222222222222222222222222222222 3 4 1
+author Bob
+author-time 172800
+summary Second commit
+filename file.txt
+\tlast line
+";
+        let lines_blame = parse_line_porcelain(output);
+        assert_eq!(lines_blame.len(), 4);
+        assert!(matches!(lines_blame[2], LineBlame::Uncommitted));
+        match (&lines_blame[0], &lines_blame[1], &lines_blame[3]) {
+            (LineBlame::Committed(first), LineBlame::Committed(second), LineBlame::Committed(last)) => {
+                assert!(Arc::ptr_eq(first, second));
+                assert_eq!(first.author, "Alice");
+                assert_eq!(first.time, 86400);
+                assert_eq!(first.summary, "First commit");
+                assert_eq!(last.id, "2222222222222222222222222222222222222222");
+                assert_eq!(last.author, "Bob");
+                assert_eq!(format_date(last.time), "1970-01-03");
+            }
+            _ => panic!("expected committed lines"),
+        }
+    }
+
+    #[test]
+    fn line_origins_follow_edits() {
+        let loaded_lines = lines("a\nb\nc\nd");
+        assert_eq!(line_origins(&loaded_lines, &loaded_lines), vec![Some(0), Some(1), Some(2), Some(3)]);
+        // inserted line
+        assert_eq!(line_origins(&loaded_lines, &lines("a\nx\nb\nc\nd")), vec![Some(0), None, Some(1), Some(2), Some(3)]);
+        // removed line
+        assert_eq!(line_origins(&loaded_lines, &lines("a\nc\nd")), vec![Some(0), Some(2), Some(3)]);
+        // modified line
+        assert_eq!(line_origins(&loaded_lines, &lines("a\nb\nC\nd")), vec![Some(0), Some(1), None, Some(3)]);
+        // modified first and last lines
+        assert_eq!(line_origins(&loaded_lines, &lines("A\nb\nc\nD\ne")), vec![None, Some(1), Some(2), None, None]);
+    }
+
+    #[test]
+    fn read_blame_of_edited_file() {
+        let repository = temp_repository("blame");
+        let directory = repository.path();
+        let path = directory.join("file.txt");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        git(directory, &["add", "file.txt"]);
+        git(directory, &["commit", "-q", "-m", "first"]);
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        git(directory, &["commit", "-q", "-a", "-m", "second"]);
+        fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+
+        // document differs from the file on disk
+        let lines_blame = read_blame(&path, "one\nTWO\nthree\n").unwrap();
+        let untracked_path = directory.join("untracked.txt");
+        fs::write(&untracked_path, "x").unwrap();
+        let untracked_error = read_blame(&untracked_path, "x");
+
+        assert_eq!(lines_blame.len(), 3);
+        assert_ne!(commit_id(&lines_blame[0]), commit_id(&lines_blame[2]));
+        assert!(matches!(lines_blame[1], LineBlame::Uncommitted));
+        match &lines_blame[2] {
+            LineBlame::Committed(commit) => {
+                assert_eq!(commit.author, "test");
+                assert_eq!(commit.summary, "second");
+            }
+            LineBlame::Uncommitted => panic!("expected committed line"),
+        }
+        assert!(untracked_error.is_err());
+    }
+}
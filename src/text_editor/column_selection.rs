@@ -0,0 +1,241 @@
+use eframe::egui::{Color32, Pos2, Rect, Rounding, Ui};
+use eframe::epaint::{RectShape, Shape};
+use crate::text_editor::{BulkAction, InactivityPeriod, Pos, State, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+pub(crate) enum ColumnEdit<'a> {
+    Insert(&'a str),
+    Backspace,
+    Delete,
+}
+
+// Rectangular selection: the same column range is selected on every line between anchor and head.
+// Anchor and head x are display columns, not grapheme indexes, so the block stays rectangular with wide characters.
+// Columns can be after the end of short lines, those lines are padded with spaces when edited.
+pub(crate) trait ColumnSelection {
+    fn has_column_selection(&self) -> bool;
+    fn start_column_selection(&mut self, index: Pos<usize>);
+    fn extend_column_selection(&mut self, index: Pos<usize>);
+    fn move_column_selection_head(&mut self, delta_x: isize, delta_y: isize);
+    fn column_selection_bounds(&self) -> (Pos<usize>, Pos<usize>);
+    fn column_selection_shapes(&self, first_line_index: usize) -> Vec<Shape>;
+    fn column_selection_text(&self) -> String;
+    fn edit_column_selection(&mut self, ui: &Ui, edit: ColumnEdit);
+    fn cursor_column(&self) -> Pos<usize>;
+}
+
+impl ColumnSelection for TextEditor {
+    fn has_column_selection(&self) -> bool {
+        self.column_selection_anchor.is_some() && self.column_selection_head.is_some()
+    }
+
+    fn start_column_selection(&mut self, index: Pos<usize>) {
+        self.selection_start_index = None;
+        self.selection_end_index = None;
+        self.column_selection_anchor = Some(index.clone());
+        self.column_selection_head = Some(index);
+    }
+
+    fn extend_column_selection(&mut self, mut index: Pos<usize>) {
+        if self.column_selection_anchor.is_none() {
+            self.start_column_selection(self.cursor_column());
+        }
+        index.y = index.y.min(self.lines.len() - 1);
+        self.set_cursor_y(index.y);
+        self.set_cursor_x(self.lines[index.y].grapheme_index_from_column(index.x));
+        self.column_selection_head = Some(index);
+    }
+
+    fn move_column_selection_head(&mut self, delta_x: isize, delta_y: isize) {
+        let head = if self.has_column_selection() {
+            self.column_selection_head.clone().unwrap()
+        } else {
+            self.cursor_column()
+        };
+        let index = Pos {
+            x: (head.x as isize + delta_x).max(0) as usize,
+            y: (head.y as isize + delta_y).max(0) as usize,
+        };
+        self.extend_column_selection(index);
+        self.has_pressed_arrow_key = true;
+    }
+
+    fn column_selection_bounds(&self) -> (Pos<usize>, Pos<usize>) {
+        let anchor = self.column_selection_anchor.as_ref().unwrap();
+        let head = self.column_selection_head.as_ref().unwrap();
+        (Pos { x: anchor.x.min(head.x), y: anchor.y.min(head.y) }, Pos { x: anchor.x.max(head.x), y: anchor.y.max(head.y) })
+    }
+
+    fn column_selection_shapes(&self, first_line_index: usize) -> Vec<Shape> {
+        let (top_left, bottom_right) = self.column_selection_bounds();
+        let mut shapes = vec![];
+        let first_y = top_left.y.max(first_line_index);
+        if first_y > bottom_right.y {
+            return shapes;
+        }
+        // a zero width block is displayed as a caret on each line
+        let width = if top_left.x == bottom_right.x { 2.0 } else { self.column_to_x(bottom_right.x) - self.column_to_x(top_left.x) };
+        shapes.push(Shape::Rect(RectShape {
+            rect: Rect {
+                min: Pos2 { x: self.column_to_x(top_left.x), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(first_y, first_line_index) },
+                max: Pos2 { x: self.column_to_x(top_left.x) + width, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(bottom_right.y, first_line_index) + self.line_height },
+            },
+            fill: Color32::LIGHT_BLUE,
+            rounding: Rounding::ZERO,
+            stroke: Default::default(),
+            fill_texture_id: Default::default(),
+            uv: Rect::ZERO,
+        }));
+        shapes
+    }
+
+    fn column_selection_text(&self) -> String {
+        let (top_left, bottom_right) = self.column_selection_bounds();
+        let width = bottom_right.x - top_left.x;
+        self.lines[top_left.y..=bottom_right.y.min(self.lines.len() - 1)].iter().map(|line| {
+            // short lines are padded so the copied block stays rectangular
+            let start_x_index = line.byte_index_from_grapheme_index(line.grapheme_index_from_column(top_left.x));
+            let end_x_index = line.byte_index_from_grapheme_index(line.grapheme_index_from_column(bottom_right.x));
+            let row = &line[start_x_index..end_x_index];
+            format!("{}{}", row, " ".repeat(width.saturating_sub(row.columns_count())))
+        }).collect::<Vec<String>>().join("\n")
+    }
+
+    fn edit_column_selection(&mut self, ui: &Ui, edit: ColumnEdit) {
+        let (top_left, bottom_right) = self.column_selection_bounds();
+        let end_y = bottom_right.y.min(self.lines.len() - 1);
+        let rows_count = end_y - top_left.y + 1;
+        let start_x = top_left.x;
+        let end_x = bottom_right.x;
+        let mut rows_text: Vec<&str> = vec![""; rows_count];
+        // With a zero width block, backspace and delete remove one grapheme on each line.
+        let mut grapheme_delta = (0, 0);
+        match edit {
+            ColumnEdit::Insert(text) => {
+                // one text line per row, text lines are cycled when their count differs from the rows count
+                let text_lines = text.split('\n').collect::<Vec<&str>>();
+                rows_text = (0..rows_count).map(|i| text_lines[i % text_lines.len()]).collect();
+            }
+            ColumnEdit::Backspace if start_x == end_x => {
+                if start_x == 0 {
+                    return;
+                }
+                grapheme_delta = (1, 0);
+            }
+            ColumnEdit::Delete if start_x == end_x => grapheme_delta = (0, 1),
+            _ => {}
+        }
+
+        let mut new_lines = Vec::with_capacity(rows_count);
+        let mut column_x = None;
+        for (i, line) in self.lines[top_left.y..=end_y].iter().enumerate() {
+            let line_columns = line.columns_count();
+            if line_columns < start_x && rows_text[i].is_empty() {
+                // nothing to remove on this line and nothing to insert, don't pad it.
+                new_lines.push(line.clone());
+                continue;
+            }
+            let mut new_line = line.clone();
+            if line_columns < start_x {
+                new_line.push_str(" ".repeat(start_x - line_columns).as_str());
+            }
+            let line_len = new_line.grapheme_count();
+            let start_index = new_line.grapheme_index_from_column(start_x).saturating_sub(grapheme_delta.0);
+            let end_index = (new_line.grapheme_index_from_column(end_x) + grapheme_delta.1).min(line_len.max(start_index));
+            let start_x_index = new_line.byte_index_from_grapheme_index(start_index);
+            let end_x_index = new_line.byte_index_from_grapheme_index(end_index);
+            new_line.replace_range(start_x_index..end_x_index, rows_text[i]);
+            if column_x.is_none() {
+                column_x = Some(new_line.column_from_grapheme_index(start_index + rows_text[i].grapheme_count()));
+            }
+            new_lines.push(new_line);
+        }
+        let previous_history_len = self.history.len();
+        if !self.replace_lines(ui, top_left.y, end_y, new_lines) {
+            return;
+        }
+        // Typing in a block is merged into one history entry, as typing in a line is by the unsaved state.
+        // A save in between keeps them apart, otherwise the merged entry would match the saved history length.
+        let is_continuation = self.column_edit_history_len == Some(previous_history_len)
+            && self.saved_history_len != Some(previous_history_len);
+        if is_continuation {
+            merge_column_edit(&mut self.history, ui.input(|input| input.time));
+        }
+        self.column_edit_history_len = Some(self.history.len());
+        // Selection collapses to a zero width block after the edit, so typing goes on each line.
+        let column_x = column_x.unwrap_or(start_x);
+        self.column_selection_anchor = Some(Pos { x: column_x, y: top_left.y });
+        self.column_selection_head = Some(Pos { x: column_x, y: end_y });
+        self.set_cursor_y(end_y);
+        self.set_cursor_x(self.lines[end_y].grapheme_index_from_column(column_x));
+    }
+
+    fn cursor_column(&self) -> Pos<usize> {
+        Pos { x: self.lines[self.cursor_index.y].column_from_grapheme_index(self.cursor_index.x), y: self.cursor_index.y }
+    }
+}
+
+// Merge the last history entry into the previous one when both replace the same lines within the inactivity period.
+// The previous entry keeps the lines from before both edits, so one undo reverts them together.
+fn merge_column_edit(history: &mut Vec<State>, time: f64) -> bool {
+    if history.len() < 2 {
+        return false;
+    }
+    let previous = &history[history.len() - 2];
+    let last = &history[history.len() - 1];
+    let (previous_action, last_action) = match (&previous.bulk_action, &last.bulk_action) {
+        (BulkAction::AddText(previous_action), BulkAction::AddText(last_action)) => (previous_action, last_action),
+        _ => return false,
+    };
+    let is_same_range = previous_action.start_index == last_action.start_index
+        && previous_action.end_index == last_action.end_index
+        && previous_action.lines.len() == last_action.lines.len();
+    if !is_same_range || time - previous.created_at >= InactivityPeriod {
+        return false;
+    }
+    history.pop();
+    history.last_mut().unwrap().created_at = time;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text_editor::{BulkAction, Pos, State, TextAction};
+    use super::merge_column_edit;
+
+    fn state(created_at: f64, start_index: usize, end_index: usize, lines: &[&str]) -> State {
+        State {
+            created_at,
+            cursor_index: Pos { x: 0, y: start_index },
+            cursor_pos: Pos { x: 0.0, y: 0.0 },
+            bulk_action: BulkAction::AddText(TextAction { start_index, end_index, lines: lines.iter().map(|line| line.to_string()).collect() }),
+        }
+    }
+
+    fn lines(state: &State) -> Vec<String> {
+        match &state.bulk_action {
+            BulkAction::AddText(action) | BulkAction::RemoveText(action) => action.lines.clone(),
+        }
+    }
+
+    #[test]
+    fn consecutive_edits_of_a_block_are_merged() {
+        let mut history = vec![state(1.0, 2, 3, &["ab", "cd"]), state(1.5, 2, 3, &["xab", "xcd"])];
+        assert!(merge_column_edit(&mut history, 1.5));
+        assert_eq!(history.len(), 1);
+        // undo restores the lines from before the first edit
+        assert_eq!(lines(&history[0]), vec!["ab", "cd"]);
+        assert_eq!(history[0].created_at, 1.5);
+    }
+
+    #[test]
+    fn edits_of_other_lines_or_after_inactivity_are_kept_apart() {
+        let mut history = vec![state(1.0, 2, 3, &["ab", "cd"]), state(1.5, 3, 4, &["cd", "ef"])];
+        assert!(!merge_column_edit(&mut history, 1.5));
+        assert_eq!(history.len(), 2);
+
+        let mut history = vec![state(1.0, 2, 3, &["ab", "cd"]), state(4.0, 2, 3, &["xab", "xcd"])];
+        assert!(!merge_column_edit(&mut history, 4.0));
+        assert_eq!(history.len(), 2);
+    }
+}
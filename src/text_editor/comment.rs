@@ -0,0 +1,156 @@
+use eframe::egui::Ui;
+use unicode_segmentation::UnicodeSegmentation;
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+pub(crate) trait ToggleComment {
+    fn toggle_line_comment(&mut self, ui: &Ui);
+    fn toggle_block_comment(&mut self, ui: &Ui);
+}
+
+impl ToggleComment for TextEditor {
+    fn toggle_line_comment(&mut self, ui: &Ui) {
+        // Languages without line comment (e.g: html) get each line wrapped into a block comment instead.
+        let (prefix, suffix) = if self.options.language.line_comment.is_some() {
+            (self.options.language.line_comment.clone().unwrap(), String::default())
+        } else if self.options.language.block_comment.is_some() {
+            self.options.language.block_comment.clone().unwrap()
+        } else {
+            return;
+        };
+        let (start_y, end_y) = self.selected_line_range();
+        let lines = &self.lines[start_y..=end_y];
+        let should_uncomment = lines.iter().filter(|line| !line.trim().is_empty())
+            .all(|line| line.trim_start().starts_with(prefix.as_str()) && line.trim_end().ends_with(suffix.as_str()));
+        let indentation = lines.iter().filter(|line| !line.trim().is_empty())
+            .map(|line| leading_whitespace_count(line)).min().unwrap_or(0);
+
+        let mut new_lines = Vec::with_capacity(lines.len());
+        // For each line: column at which text has been added or removed, and the char count difference.
+        let mut shifts: Vec<(usize, isize)> = Vec::with_capacity(lines.len());
+        for line in lines.iter() {
+            if line.trim().is_empty() {
+                new_lines.push(line.clone());
+                shifts.push((0, 0));
+            } else if should_uncomment {
+                let column = leading_whitespace_count(line);
+                let mut removed_len = prefix.grapheme_count();
+                let mut new_line = line.clone();
+                let start = new_line.byte_index_from_grapheme_index(column);
+                new_line.replace_range(start..start + prefix.len(), "");
+                if new_line[start..].starts_with(' ') {
+                    new_line.remove(start);
+                    removed_len += 1;
+                }
+                if !suffix.is_empty() {
+                    let end = new_line.trim_end().len() - suffix.len();
+                    new_line.replace_range(end..end + suffix.len(), "");
+                    if new_line[..end].ends_with(' ') {
+                        new_line.remove(end - 1);
+                    }
+                }
+                new_lines.push(new_line);
+                shifts.push((column, -(removed_len as isize)));
+            } else {
+                let mut new_line = line.clone();
+                if !suffix.is_empty() {
+                    let end = new_line.trim_end().len();
+                    new_line.insert_str(end, format!(" {}", suffix).as_str());
+                }
+                let start = new_line.byte_index_from_grapheme_index(indentation);
+                new_line.insert_str(start, format!("{} ", prefix).as_str());
+                new_lines.push(new_line);
+                shifts.push((indentation, prefix.grapheme_count() as isize + 1));
+            }
+        }
+
+        let shift = |pos: &Pos<usize>| -> Pos<usize> {
+            if pos.y < start_y || pos.y > end_y {
+                return pos.clone();
+            }
+            let (column, delta) = shifts[pos.y - start_y];
+            if pos.x < column {
+                return pos.clone();
+            }
+            Pos { x: (pos.x as isize + delta).max(column as isize) as usize, y: pos.y }
+        };
+        let selection = if self.has_selection() {
+            Some((shift(self.selection_start_index.as_ref().unwrap()), shift(self.selection_end_index.as_ref().unwrap())))
+        } else {
+            None
+        };
+        let cursor_index = shift(&self.cursor_index);
+
+        if !self.replace_lines(ui, start_y, end_y, new_lines) {
+            return;
+        }
+        self.set_cursor_y(cursor_index.y);
+        self.set_cursor_x(cursor_index.x);
+        if let Some((selection_start_index, selection_end_index)) = selection {
+            self.select_range(selection_start_index, selection_end_index);
+        }
+    }
+
+    fn toggle_block_comment(&mut self, ui: &Ui) {
+        if self.options.language.block_comment.is_none() {
+            self.toggle_line_comment(ui);
+            return;
+        }
+        let (open, close) = self.options.language.block_comment.clone().unwrap();
+        let (start_index, end_index) = if self.has_selection() {
+            (self.selection_start_index.clone().unwrap(), self.selection_end_index.clone().unwrap())
+        } else {
+            // Without selection, the cursor line content is commented
+            let line = &self.lines[self.cursor_index.y];
+            let start_x = leading_whitespace_count(line);
+            // a blank line is an empty range after its indentation
+            let end_x = line.trim_end().grapheme_count().max(start_x);
+            (Pos { x: start_x, y: self.cursor_index.y }, Pos { x: end_x, y: self.cursor_index.y })
+        };
+        let open_len = open.grapheme_count();
+        let close_len = close.grapheme_count();
+
+        let mut new_lines = self.lines[start_index.y..=end_index.y].to_vec();
+        let last = new_lines.len() - 1;
+        let start_byte = new_lines[0].byte_index_from_grapheme_index(start_index.x);
+        let end_byte = new_lines[last].byte_index_from_grapheme_index(end_index.x);
+        let is_commented = new_lines[0][start_byte..].starts_with(open.as_str())
+            && new_lines[last][..end_byte].ends_with(close.as_str())
+            && (start_index.y != end_index.y || end_index.x - start_index.x >= open_len + close_len);
+
+        let new_end_x;
+        if is_commented {
+            new_lines[last].replace_range(end_byte - close.len()..end_byte, "");
+            new_lines[0].replace_range(start_byte..start_byte + open.len(), "");
+            new_end_x = end_index.x - close_len - if start_index.y == end_index.y { open_len } else { 0 };
+        } else {
+            new_lines[last].insert_str(end_byte, close.as_str());
+            new_lines[0].insert_str(start_byte, open.as_str());
+            new_end_x = end_index.x + if start_index.y == end_index.y { open_len } else { 0 };
+        }
+        let had_selection = self.has_selection();
+
+        if !self.replace_lines(ui, start_index.y, end_index.y, new_lines) {
+            return;
+        }
+        if had_selection {
+            let selection_start_x = if is_commented { start_index.x } else { start_index.x + open_len };
+            self.set_cursor_y(end_index.y);
+            self.set_cursor_x(new_end_x);
+            self.select_range(Pos { x: selection_start_x, y: start_index.y }, Pos { x: new_end_x, y: end_index.y });
+        } else {
+            let cursor_x = if self.cursor_index.x <= start_index.x {
+                self.cursor_index.x
+            } else if is_commented {
+                self.cursor_index.x.saturating_sub(open_len).max(start_index.x)
+            } else {
+                self.cursor_index.x + open_len
+            };
+            self.set_cursor_x(cursor_x);
+        }
+    }
+}
+
+fn leading_whitespace_count(line: &str) -> usize {
+    line.graphemes(true).take_while(|g| g.chars().all(char::is_whitespace)).count()
+}
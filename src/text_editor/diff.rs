@@ -0,0 +1,378 @@
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+// A group of consecutive changed lines: left range is replaced by right range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum RowKind {
+    Equal,
+    Changed,
+    // Only on left side, right side displays a filler line
+    Deleted,
+    // Only on right side, left side displays a filler line
+    Inserted,
+}
+
+// Left and right lines displayed at the same row of the side by side view.
+#[derive(Debug, Clone)]
+pub(crate) struct DiffRow {
+    pub(crate) left: Option<usize>,
+    pub(crate) right: Option<usize>,
+    pub(crate) kind: RowKind,
+}
+
+// Edits to transform a into b, computed with Myers algorithm in linear space.
+pub(crate) fn diff<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(a.len().max(b.len()));
+    myers(a, b, &mut edits);
+    edits
+}
+
+// Common prefix and suffix are skipped, the middle snake of the remaining shortest edit script splits it
+// into two scripts with fewer edits, computed recursively. Memory stays linear in the sequences length.
+fn myers<T: PartialEq>(a: &[T], b: &[T], edits: &mut Vec<Edit>) {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let a = &a[prefix..a.len() - suffix];
+    let b = &b[prefix..b.len() - suffix];
+    edits.extend(std::iter::repeat(Edit::Equal).take(prefix));
+    if a.is_empty() {
+        edits.extend(std::iter::repeat(Edit::Insert).take(b.len()));
+    } else if b.is_empty() {
+        edits.extend(std::iter::repeat(Edit::Delete).take(a.len()));
+    } else {
+        // both sides start and end with different items: at least 2 edits, each half has fewer
+        let (start, end) = middle_snake(a, b);
+        myers(&a[..start.0], &b[..start.1], edits);
+        edits.extend(std::iter::repeat(Edit::Equal).take(end.0 - start.0));
+        myers(&a[end.0..], &b[end.1..], edits);
+    }
+    edits.extend(std::iter::repeat(Edit::Equal).take(suffix));
+}
+
+// Start and end of the snake in the middle of a shortest edit script, found by searching from both ends
+// until the furthest paths overlap. forward[k] is the furthest x on diagonal k = x - y from the start,
+// backward[k] the furthest x on diagonal k from the end, in reversed sequences.
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T]) -> ((usize, usize), (usize, usize)) {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let delta = n - m;
+    let is_odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let mut forward = vec![0isize; (2 * max + 3) as usize];
+    let mut backward = vec![0isize; (2 * max + 3) as usize];
+    let furthest = |v: &[isize], d: isize, k: isize| {
+        let i = (k + offset) as usize;
+        if k == -d || (k != d && v[i - 1] < v[i + 1]) { v[i + 1] } else { v[i - 1] + 1 }
+    };
+    for d in 0..=max {
+        let mut k = -d;
+        while k <= d {
+            let (start_x, start_y) = (furthest(&forward, d, k), furthest(&forward, d, k) - k);
+            let (mut x, mut y) = (start_x, start_y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[(k + offset) as usize] = x;
+            // backward paths of the previous round are on diagonals -(d - 1)..=d - 1
+            let backward_k = delta - k;
+            if is_odd && backward_k.abs() < d && x + backward[(backward_k + offset) as usize] >= n {
+                return ((start_x as usize, start_y as usize), (x as usize, y as usize));
+            }
+            k += 2;
+        }
+        let mut k = -d;
+        while k <= d {
+            let (start_x, start_y) = (furthest(&backward, d, k), furthest(&backward, d, k) - k);
+            let (mut x, mut y) = (start_x, start_y);
+            while x < n && y < m && a[(n - 1 - x) as usize] == b[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[(k + offset) as usize] = x;
+            let forward_k = delta - k;
+            if !is_odd && forward_k.abs() <= d && x + forward[(forward_k + offset) as usize] >= n {
+                return (((n - x) as usize, (m - y) as usize), ((n - start_x) as usize, (m - start_y) as usize));
+            }
+            k += 2;
+        }
+    }
+    unreachable!("a shortest edit script has at most n + m edits")
+}
+
+// Changed line ranges, without rows of the side by side view.
+pub(crate) fn diff_hunks<T: PartialEq>(left: &[T], right: &[T]) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = vec![];
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut is_in_hunk = false;
+    for edit in diff(left, right) {
+        if edit != Edit::Equal && !is_in_hunk {
+            hunks.push(Hunk { left: left_index..left_index, right: right_index..right_index });
+        }
+        is_in_hunk = edit != Edit::Equal;
+        match edit {
+            Edit::Equal => {
+                left_index += 1;
+                right_index += 1;
+            }
+            Edit::Delete => left_index += 1,
+            Edit::Insert => right_index += 1,
+        }
+        if let Some(hunk) = hunks.last_mut().filter(|_| is_in_hunk) {
+            hunk.left.end = left_index;
+            hunk.right.end = right_index;
+        }
+    }
+    hunks
+}
+
+// Rows of the side by side view, and hunks with the index of their first row.
+pub(crate) fn diff_rows(left: &[String], right: &[String]) -> (Vec<DiffRow>, Vec<(Hunk, usize)>) {
+    let edits = diff(left, right);
+    let mut rows = Vec::with_capacity(edits.len());
+    let mut hunks = vec![];
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut i = 0;
+    while i < edits.len() {
+        if edits[i] == Edit::Equal {
+            rows.push(DiffRow { left: Some(left_index), right: Some(right_index), kind: RowKind::Equal });
+            left_index += 1;
+            right_index += 1;
+            i += 1;
+            continue;
+        }
+        let hunk_end = edits[i..].iter().position(|edit| *edit == Edit::Equal).map_or(edits.len(), |position| i + position);
+        let deleted_count = edits[i..hunk_end].iter().filter(|edit| **edit == Edit::Delete).count();
+        let inserted_count = hunk_end - i - deleted_count;
+        hunks.push((Hunk { left: left_index..left_index + deleted_count, right: right_index..right_index + inserted_count }, rows.len()));
+        // Deleted and inserted lines are paired as changed lines, remaining ones face filler lines.
+        for j in 0..deleted_count.max(inserted_count) {
+            let left_line = if j < deleted_count { Some(left_index + j) } else { None };
+            let right_line = if j < inserted_count { Some(right_index + j) } else { None };
+            let kind = match (left_line, right_line) {
+                (Some(_), Some(_)) => RowKind::Changed,
+                (Some(_), None) => RowKind::Deleted,
+                _ => RowKind::Inserted,
+            };
+            rows.push(DiffRow { left: left_line, right: right_line, kind });
+        }
+        left_index += deleted_count;
+        right_index += inserted_count;
+        i = hunk_end;
+    }
+    (rows, hunks)
+}
+
+// Grapheme index ranges of words removed from left line and added to right line.
+pub(crate) fn word_diff(left: &str, right: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let left_words = split_words(left);
+    let right_words = split_words(right);
+    let mut removed: Vec<Range<usize>> = vec![];
+    let mut added: Vec<Range<usize>> = vec![];
+    let (mut left_index, mut right_index) = (0, 0);
+    let (mut left_x, mut right_x) = (0, 0);
+    let push = |ranges: &mut Vec<Range<usize>>, range: Range<usize>| {
+        match ranges.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => ranges.push(range),
+        }
+    };
+    for edit in diff(&left_words, &right_words) {
+        match edit {
+            Edit::Equal => {
+                left_x += left_words[left_index].1;
+                right_x += right_words[right_index].1;
+                left_index += 1;
+                right_index += 1;
+            }
+            Edit::Delete => {
+                push(&mut removed, left_x..left_x + left_words[left_index].1);
+                left_x += left_words[left_index].1;
+                left_index += 1;
+            }
+            Edit::Insert => {
+                push(&mut added, right_x..right_x + right_words[right_index].1);
+                right_x += right_words[right_index].1;
+                right_index += 1;
+            }
+        }
+    }
+    (removed, added)
+}
+
+// Words, whitespace runs and single punctuation characters, with their grapheme count.
+fn split_words(line: &str) -> Vec<(&str, usize)> {
+    let mut words = vec![];
+    let mut start = 0;
+    let mut count = 0;
+    let mut previous_class = None;
+    for (i, grapheme) in line.grapheme_indices(true) {
+        let class = if grapheme.chars().all(char::is_alphanumeric) || grapheme == "_" { 0 } else if grapheme.chars().all(char::is_whitespace) { 1 } else { 2 };
+        if count > 0 && (Some(class) != previous_class || class == 2) {
+            words.push((&line[start..i], count));
+            start = i;
+            count = 0;
+        }
+        count += 1;
+        previous_class = Some(class);
+    }
+    if count > 0 {
+        words.push((&line[start..], count));
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Applies edits to a, checking equal lines match, and returns the result
+    fn apply<T: PartialEq + Clone + std::fmt::Debug>(a: &[T], b: &[T], edits: &[Edit]) -> Vec<T> {
+        let (mut x, mut y) = (0, 0);
+        let mut result = vec![];
+        for edit in edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(a[x], b[y]);
+                    result.push(a[x].clone());
+                    x += 1;
+                    y += 1;
+                }
+                Edit::Delete => x += 1,
+                Edit::Insert => {
+                    result.push(b[y].clone());
+                    y += 1;
+                }
+            }
+        }
+        assert_eq!((x, y), (a.len(), b.len()));
+        result
+    }
+
+    fn lcs_len(a: &[char], b: &[char]) -> usize {
+        let mut lengths = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in 0..a.len() {
+            for j in 0..b.len() {
+                lengths[i + 1][j + 1] = if a[i] == b[j] { lengths[i][j] + 1 } else { lengths[i][j + 1].max(lengths[i + 1][j]) };
+            }
+        }
+        lengths[a.len()][b.len()]
+    }
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    #[test]
+    fn diff_empty_sides() {
+        assert_eq!(diff::<char>(&[], &[]), vec![]);
+        assert_eq!(diff(&chars("abc"), &[]), vec![Edit::Delete; 3]);
+        assert_eq!(diff(&[], &chars("abc")), vec![Edit::Insert; 3]);
+    }
+
+    #[test]
+    fn diff_prefix_and_suffix_only_changes() {
+        assert_eq!(diff(&chars("abc"), &chars("abcde")), vec![Edit::Equal, Edit::Equal, Edit::Equal, Edit::Insert, Edit::Insert]);
+        assert_eq!(diff(&chars("abcde"), &chars("cde")), vec![Edit::Delete, Edit::Delete, Edit::Equal, Edit::Equal, Edit::Equal]);
+        assert_eq!(diff(&chars("abc"), &chars("abc")), vec![Edit::Equal; 3]);
+    }
+
+    #[test]
+    fn diff_is_a_minimal_valid_script() {
+        // every pair of strings of length up to 4 over a 3 letters alphabet
+        let mut texts = vec![String::new()];
+        for _ in 0..4 {
+            let longer = texts.iter().filter(|text| text.len() == texts.last().unwrap().len())
+                .flat_map(|text| ["a", "b", "c"].map(|c| format!("{}{}", text, c))).collect::<Vec<String>>();
+            texts.extend(longer);
+        }
+        for a in texts.iter().map(|text| chars(text)) {
+            for b in texts.iter().map(|text| chars(text)) {
+                let edits = diff(&a, &b);
+                assert_eq!(apply(&a, &b, &edits), b);
+                let equal_count = edits.iter().filter(|edit| **edit == Edit::Equal).count();
+                assert_eq!(equal_count, lcs_len(&a, &b), "{:?} {:?} {:?}", a, b, edits);
+            }
+        }
+    }
+
+    #[test]
+    fn diff_is_minimal_on_longer_sequences() {
+        // every pair of strings of length up to 7 over a 2 letters alphabet, middle snakes are split several times
+        let mut texts = vec![String::new()];
+        for _ in 0..7 {
+            let longer = texts.iter().filter(|text| text.len() == texts.last().unwrap().len())
+                .flat_map(|text| ["a", "b"].map(|c| format!("{}{}", text, c))).collect::<Vec<String>>();
+            texts.extend(longer);
+        }
+        for a in texts.iter().map(|text| chars(text)) {
+            for b in texts.iter().map(|text| chars(text)) {
+                let edits = diff(&a, &b);
+                assert_eq!(apply(&a, &b, &edits), b);
+                assert_eq!(edits.iter().filter(|edit| **edit == Edit::Equal).count(), lcs_len(&a, &b), "{:?} {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn diff_of_unrelated_large_sequences() {
+        // memory stays linear, a trace of every round of the search would be quadratic
+        let a = (0..2_000).collect::<Vec<usize>>();
+        let b = (2_000..4_000).collect::<Vec<usize>>();
+        let edits = diff(&a, &b);
+        assert_eq!(edits.iter().filter(|edit| **edit == Edit::Delete).count(), 2_000);
+        assert_eq!(edits.iter().filter(|edit| **edit == Edit::Insert).count(), 2_000);
+    }
+
+    #[test]
+    fn diff_hunks_group_consecutive_changes() {
+        let hunks = diff_hunks(&chars("abcdef"), &chars("aXcdYYf"));
+        assert_eq!(hunks, vec![Hunk { left: 1..2, right: 1..2 }, Hunk { left: 4..5, right: 4..6 }]);
+        assert_eq!(diff_hunks(&chars("abc"), &chars("abc")), vec![]);
+        assert_eq!(diff_hunks(&chars(""), &chars("ab")), vec![Hunk { left: 0..0, right: 0..2 }]);
+    }
+
+    #[test]
+    fn diff_rows_align_changed_lines_and_fillers() {
+        let lines = |text: &str| text.split(' ').map(|line| line.to_string()).collect::<Vec<String>>();
+        let (rows, hunks) = diff_rows(&lines("a b c d"), &lines("a B e f d"));
+        let rows = rows.iter().map(|row| (row.left, row.right, row.kind)).collect::<Vec<_>>();
+        assert_eq!(rows, vec![
+            (Some(0), Some(0), RowKind::Equal),
+            (Some(1), Some(1), RowKind::Changed),
+            (Some(2), Some(2), RowKind::Changed),
+            (None, Some(3), RowKind::Inserted),
+            (Some(3), Some(4), RowKind::Equal),
+        ]);
+        assert_eq!(hunks, vec![(Hunk { left: 1..3, right: 1..4 }, 1)]);
+
+        let (rows, hunks) = diff_rows(&lines("a b c"), &lines("a"));
+        let rows = rows.iter().map(|row| (row.left, row.right, row.kind)).collect::<Vec<_>>();
+        assert_eq!(rows, vec![(Some(0), Some(0), RowKind::Equal), (Some(1), None, RowKind::Deleted), (Some(2), None, RowKind::Deleted)]);
+        assert_eq!(hunks, vec![(Hunk { left: 1..3, right: 1..1 }, 1)]);
+    }
+
+    #[test]
+    fn word_diff_ranges_are_grapheme_indices() {
+        assert_eq!(word_diff("let a = 1;", "let b = 1;"), (vec![4..5], vec![4..5]));
+        assert_eq!(word_diff("foo bar", "foo bar baz"), (vec![], vec![7..11]));
+        assert_eq!(word_diff("é x", "é yy"), (vec![2..3], vec![2..4]));
+        assert_eq!(word_diff("", "abc"), (vec![], vec![0..3]));
+        // consecutive removed words are merged
+        assert_eq!(word_diff("a b c", "a"), (vec![1..5], vec![]));
+    }
+}
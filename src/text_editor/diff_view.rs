@@ -0,0 +1,276 @@
+use eframe::egui;
+use eframe::egui::{Color32, Event, Key, Pos2, Rect, Ui};
+use crate::text_editor::{Pos, TextEditor, TextEditorOptions};
+use crate::text_editor::Selection;
+use crate::text_editor::diff::{diff_rows, word_diff, DiffRow, Hunk, RowKind};
+use crate::text_editor::split_view::Buffer;
+
+const DiffSeparatorWidth: f32 = 4.0;
+// Lines displayed above a hunk when navigating to it
+const HunkContextLines: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffSide {
+    Left,
+    Right,
+}
+
+impl DiffSide {
+    fn index(&self) -> usize {
+        match self {
+            DiffSide::Left => 0,
+            DiffSide::Right => 1,
+        }
+    }
+}
+
+// Lines of one side replaced when a hunk was accepted, to undo it.
+struct HunkAcceptance {
+    modified_side: DiffSide,
+    start: usize,
+    inserted_count: usize,
+    replaced_lines: Vec<String>,
+}
+
+// Two texts displayed side by side, changed lines of each side facing each other. Lines only present on one side face
+// filler lines so both sides stay aligned, and both editors scroll together.
+// Displayed buffers are not editable: texts change by accepting a hunk from one side into the other one.
+pub struct DiffView {
+    editors: [TextEditor; 2],
+    lines: [Vec<String>; 2],
+    rows: Vec<DiffRow>,
+    // Hunks with the index of their first row
+    hunks: Vec<(Hunk, usize)>,
+    current_hunk_index: Option<usize>,
+    history: Vec<HunkAcceptance>,
+    last_scroll_offset_y: f32,
+    // Side receiving keyboard input (navigation, copy)
+    focused_side_index: usize,
+}
+
+impl DiffView {
+    pub fn new(creation_context: &eframe::CreationContext<'_>, left_text: String, right_text: String, options: TextEditorOptions) -> Self {
+        Self::from_editor(TextEditor::new(creation_context, String::new(), options), left_text, right_text)
+    }
+
+    // Left side is displayed by the given editor, its content is replaced.
+    pub(crate) fn from_editor(mut left_editor: TextEditor, left_text: String, right_text: String) -> Self {
+        // Scope headers of each side would not be aligned
+        left_editor.options.sticky_scroll = false;
+        left_editor.options.read_only = true;
+        // Both sides are rendered in the same frame, the view has its own glyph brushes.
+        let right_editor = left_editor.new_view();
+        let mut diff_view = Self {
+            editors: [left_editor, right_editor],
+            lines: [Self::split_lines(&left_text), Self::split_lines(&right_text)],
+            rows: vec![],
+            hunks: vec![],
+            current_hunk_index: None,
+            history: vec![],
+            last_scroll_offset_y: 0.0,
+            focused_side_index: 0,
+        };
+        diff_view.refresh();
+        diff_view
+    }
+
+    // Replaces compared texts, accepted hunks can no longer be undone.
+    pub fn set_texts(&mut self, left_text: String, right_text: String) {
+        self.lines = [Self::split_lines(&left_text), Self::split_lines(&right_text)];
+        self.history.clear();
+        self.current_hunk_index = None;
+        self.refresh();
+    }
+
+    pub fn text(&self, side: DiffSide) -> String {
+        self.lines[side.index()].join("\n")
+    }
+
+    pub fn hunks(&self) -> impl Iterator<Item=&Hunk> {
+        self.hunks.iter().map(|(hunk, _)| hunk)
+    }
+
+    pub fn current_hunk_index(&self) -> Option<usize> {
+        self.current_hunk_index
+    }
+
+    pub fn next_hunk(&mut self) {
+        if self.hunks.is_empty() {
+            return;
+        }
+        let next_index = self.current_hunk_index.map_or(0, |index| (index + 1) % self.hunks.len());
+        self.go_to_hunk(next_index);
+    }
+
+    pub fn previous_hunk(&mut self) {
+        if self.hunks.is_empty() {
+            return;
+        }
+        let len = self.hunks.len();
+        let previous_index = self.current_hunk_index.map_or(len - 1, |index| (index + len - 1) % len);
+        self.go_to_hunk(previous_index);
+    }
+
+    // Current hunk of the accepted side replaces the one of the other side.
+    pub fn accept(&mut self, accepted_side: DiffSide) {
+        let hunk = match self.current_hunk_index.and_then(|index| self.hunks.get(index)) {
+            Some((hunk, _)) => hunk.clone(),
+            None => return,
+        };
+        let (accepted_range, modified_range, modified_side) = match accepted_side {
+            DiffSide::Left => (hunk.left, hunk.right, DiffSide::Right),
+            DiffSide::Right => (hunk.right, hunk.left, DiffSide::Left),
+        };
+        let accepted_lines = self.lines[accepted_side.index()][accepted_range].to_vec();
+        let inserted_count = accepted_lines.len();
+        let start = modified_range.start;
+        let replaced_lines = self.lines[modified_side.index()].splice(modified_range, accepted_lines).collect::<Vec<String>>();
+        self.history.push(HunkAcceptance { modified_side, start, inserted_count, replaced_lines });
+        self.refresh();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(acceptance) = self.history.pop() {
+            let lines = &mut self.lines[acceptance.modified_side.index()];
+            lines.splice(acceptance.start..acceptance.start + acceptance.inserted_count, acceptance.replaced_lines);
+            self.refresh();
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, ui: &mut Ui) {
+        self.handle_shortcuts(ui);
+        let toolbar_bottom = self.toolbar(ui);
+        let max_rect = ui.max_rect();
+        let x = max_rect.min.x + (max_rect.width() - DiffSeparatorWidth) / 2.0;
+        let rects = [
+            Rect { min: Pos2 { x: max_rect.min.x, y: toolbar_bottom }, max: Pos2 { x, y: max_rect.max.y } },
+            Rect { min: Pos2 { x: x + DiffSeparatorWidth, y: toolbar_bottom }, max: max_rect.max },
+        ];
+        let maybe_pressed_pos = ui.input(|input| if input.pointer.any_pressed() { input.pointer.interact_pos() } else { None });
+        if let Some(pressed_side_index) = maybe_pressed_pos.and_then(|pos| rects.iter().position(|rect| rect.contains(pos))) {
+            self.focused_side_index = pressed_side_index;
+        }
+        for (side_index, rect) in rects.iter().enumerate() {
+            let editor = &mut self.editors[side_index];
+            editor.has_keyboard_focus = side_index == self.focused_side_index;
+            ui.allocate_ui_at_rect(*rect, |ui| ui.push_id(side_index, |ui| editor.ui(ctx, ui)));
+        }
+        ui.painter().rect_filled(Rect { min: Pos2 { x, y: toolbar_bottom }, max: Pos2 { x: x + DiffSeparatorWidth, y: max_rect.max.y } },
+                                 0.0, Color32::LIGHT_GRAY);
+        self.sync_scroll();
+    }
+
+    fn handle_shortcuts(&mut self, ui: &Ui) {
+        let events = ui.input(|input| input.events.clone());
+        for event in events {
+            match event {
+                // F7 goes to next hunk, shift+F7 to the previous one
+                Event::Key { key: Key::F7, pressed: true, modifiers, .. } => {
+                    if modifiers.shift { self.previous_hunk() } else { self.next_hunk() }
+                }
+                Event::Key { key: Key::Z, pressed: true, modifiers, .. } if modifiers.ctrl => self.undo(),
+                _ => {}
+            }
+        }
+    }
+
+    // Returns y position of the toolbar bottom
+    fn toolbar(&mut self, ui: &mut Ui) -> f32 {
+        let response = ui.horizontal(|ui| {
+            if ui.button("Previous").clicked() {
+                self.previous_hunk();
+            }
+            if ui.button("Next").clicked() {
+                self.next_hunk();
+            }
+            let has_current_hunk = self.current_hunk_index.is_some();
+            if ui.add_enabled(has_current_hunk, egui::Button::new("Accept left")).clicked() {
+                self.accept(DiffSide::Left);
+            }
+            if ui.add_enabled(has_current_hunk, egui::Button::new("Accept right")).clicked() {
+                self.accept(DiffSide::Right);
+            }
+            if ui.add_enabled(!self.history.is_empty(), egui::Button::new("Undo")).clicked() {
+                self.undo();
+            }
+            match self.current_hunk_index {
+                Some(index) => ui.label(format!("Hunk {}/{}", index + 1, self.hunks.len())),
+                None => ui.label(format!("{} hunks", self.hunks.len())),
+            };
+        }).response;
+        response.rect.max.y + ui.spacing().item_spacing.y
+    }
+
+    // Scrolling one side scrolls the other one to the same row.
+    fn sync_scroll(&mut self) {
+        let [left_offset_y, right_offset_y] = [self.editors[0].scroll_offset.y, self.editors[1].scroll_offset.y];
+        if left_offset_y != self.last_scroll_offset_y {
+            self.editors[1].scroll_to_offset_y = Some(left_offset_y);
+            self.last_scroll_offset_y = left_offset_y;
+        } else if right_offset_y != self.last_scroll_offset_y {
+            self.editors[0].scroll_to_offset_y = Some(right_offset_y);
+            self.last_scroll_offset_y = right_offset_y;
+        }
+    }
+
+    fn go_to_hunk(&mut self, hunk_index: usize) {
+        self.current_hunk_index = Some(hunk_index);
+        let row = self.hunks[hunk_index].1;
+        for editor in self.editors.iter_mut() {
+            editor.reset_selection();
+            editor.set_cursor_y(row);
+            editor.set_cursor_x(0);
+            editor.scroll_to_offset_y = Some(row.saturating_sub(HunkContextLines) as f32 * editor.line_height);
+        }
+        self.last_scroll_offset_y = self.editors[0].scroll_to_offset_y.unwrap_or_default();
+    }
+
+    // Computes diff again and fills editors with aligned lines.
+    fn refresh(&mut self) {
+        let (rows, hunks) = diff_rows(&self.lines[0], &self.lines[1]);
+        self.rows = rows;
+        self.hunks = hunks;
+        self.current_hunk_index = match self.current_hunk_index {
+            _ if self.hunks.is_empty() => None,
+            Some(index) => Some(index.min(self.hunks.len() - 1)),
+            None => None,
+        };
+        for side in [DiffSide::Left, DiffSide::Right] {
+            let side_index = side.index();
+            let theme = self.editors[side_index].options.theme.clone();
+            let mut displayed_lines = Vec::with_capacity(self.rows.len());
+            let mut line_numbers = Vec::with_capacity(self.rows.len());
+            let mut line_backgrounds = vec![];
+            let mut range_backgrounds = vec![];
+            for (row_index, row) in self.rows.iter().enumerate() {
+                let line_index = if side == DiffSide::Left { row.left } else { row.right };
+                displayed_lines.push(line_index.map_or_else(String::new, |line_index| self.lines[side_index][line_index].clone()));
+                line_numbers.push(line_index);
+                let color = match (row.kind, line_index) {
+                    (RowKind::Equal, _) => continue,
+                    (_, None) => theme.diff_filler_background,
+                    (RowKind::Changed, _) => theme.diff_changed_background,
+                    (RowKind::Deleted, _) => theme.diff_removed_background,
+                    (RowKind::Inserted, _) => theme.diff_added_background,
+                };
+                line_backgrounds.push((row_index, color));
+                if let (RowKind::Changed, Some(left), Some(right)) = (row.kind, row.left, row.right) {
+                    let (removed, added) = word_diff(&self.lines[0][left], &self.lines[1][right]);
+                    let (ranges, color) = if side == DiffSide::Left { (removed, theme.diff_removed_word) } else { (added, theme.diff_added_word) };
+                    range_backgrounds.extend(ranges.into_iter()
+                        .map(|range| (Pos { x: range.start, y: row_index }, Pos { x: range.end, y: row_index }, color)));
+                }
+            }
+            let editor = &mut self.editors[side_index];
+            editor.take_buffer();
+            editor.put_buffer(Buffer::new(displayed_lines));
+            editor.line_backgrounds = line_backgrounds;
+            editor.range_backgrounds = range_backgrounds;
+            editor.gutter_line_numbers = Some(line_numbers);
+        }
+    }
+
+    fn split_lines(text: &str) -> Vec<String> {
+        text.split("\n").map(|s| s.to_string()).collect()
+    }
+}
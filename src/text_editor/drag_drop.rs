@@ -0,0 +1,124 @@
+use eframe::egui::{Color32, CursorIcon, Pos2, Rect, Rounding, Ui};
+use eframe::epaint::{RectShape, Shape};
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+// Selected text can be dragged and dropped elsewhere: it is moved, or copied when ctrl is hold.
+pub(crate) trait DragDropSelection {
+    fn is_pointer_on_selection(&self, pointer: Pos2) -> bool;
+    fn start_selection_drag(&mut self);
+    fn on_selection_drag(&mut self, ui: &Ui, index: Pos<usize>);
+    fn on_selection_drop(&mut self, ui: &Ui);
+    fn drop_caret_shape(&self, first_line_index: usize) -> Option<Shape>;
+}
+
+impl DragDropSelection for TextEditor {
+    fn is_pointer_on_selection(&self, pointer: Pos2) -> bool {
+        self.has_selection() && self.selection_shapes(self.first_line_index()).iter()
+            .any(|shape| shape.visual_bounding_rect().contains(pointer))
+    }
+
+    fn start_selection_drag(&mut self) {
+        self.is_dragging_selection = true;
+        self.drop_index = None;
+    }
+
+    fn on_selection_drag(&mut self, ui: &Ui, mut index: Pos<usize>) {
+        index.y = index.y.min(self.lines.len() - 1);
+        index.x = index.x.min(self.lines[index.y].grapheme_count());
+        self.drop_index = Some(index);
+        ui.output_mut(|output| output.cursor_icon = if ui.input(|input| input.modifiers.ctrl) { CursorIcon::Copy } else { CursorIcon::Grabbing });
+    }
+
+    fn on_selection_drop(&mut self, ui: &Ui) {
+        self.is_dragging_selection = false;
+        if self.drop_index.is_none() || !self.has_selection() {
+            return;
+        }
+        let drop_index = self.drop_index.take().unwrap();
+        let selection_start_index = self.selection_start_index.clone().unwrap();
+        let selection_end_index = self.selection_end_index.clone().unwrap();
+        let is_copy = ui.input(|input| input.modifiers.ctrl);
+        if !is_copy && !Self::is_before(&drop_index, &selection_start_index) && !Self::is_before(&selection_end_index, &drop_index) {
+            // dropped on itself
+            self.reset_selection();
+            self.set_cursor_y(drop_index.y);
+            self.set_cursor_x(drop_index.x);
+            return;
+        }
+        let text = self.selected_text();
+        let start_y = selection_start_index.y.min(drop_index.y);
+        let end_y = selection_end_index.y.max(drop_index.y);
+        self.load_mapped_lines(start_y..end_y + 1);
+        let mut lines = self.lines[start_y..=end_y].to_vec();
+        let mut insert_index = Pos { x: drop_index.x, y: drop_index.y - start_y };
+        if !is_copy {
+            // Text is removed first, drop position has to take the removal into account when it is after the selection.
+            let start = Pos { x: selection_start_index.x, y: selection_start_index.y - start_y };
+            let end = Pos { x: selection_end_index.x, y: selection_end_index.y - start_y };
+            if !Self::is_before(&insert_index, &end) {
+                if insert_index.y == end.y {
+                    insert_index.x = start.x + insert_index.x - end.x;
+                }
+                insert_index.y -= end.y - start.y;
+            }
+            remove_text(&mut lines, &start, &end);
+        }
+        let inserted_end = insert_text(&mut lines, &insert_index, text.as_str());
+        // Remove and insert are recorded as a single history entry.
+        if !self.replace_lines(ui, start_y, end_y, lines) {
+            return;
+        }
+        let new_start = Pos { x: insert_index.x, y: insert_index.y + start_y };
+        let new_end = Pos { x: inserted_end.x, y: inserted_end.y + start_y };
+        self.set_cursor_y(new_end.y);
+        self.set_cursor_x(new_end.x);
+        self.select_range(new_start, new_end);
+    }
+
+    fn drop_caret_shape(&self, first_line_index: usize) -> Option<Shape> {
+        if !self.is_dragging_selection || self.drop_index.is_none() {
+            return None;
+        }
+        let drop_index = self.drop_index.as_ref().unwrap();
+        if drop_index.y < first_line_index {
+            return None;
+        }
+        Some(Shape::Rect(RectShape {
+            rect: Rect {
+                min: Pos2 { x: self.index_to_x(drop_index), y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(drop_index.y, first_line_index) },
+                max: Pos2 { x: self.index_to_x(drop_index) + 2.0, y: self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(drop_index.y, first_line_index) + self.line_height },
+            },
+            rounding: Rounding::ZERO,
+            fill: Color32::DARK_GRAY,
+            stroke: Default::default(),
+            fill_texture_id: Default::default(),
+            uv: Rect::ZERO,
+        }))
+    }
+}
+
+fn remove_text(lines: &mut Vec<String>, start: &Pos<usize>, end: &Pos<usize>) {
+    let start_x_index = lines[start.y].byte_index_from_grapheme_index(start.x);
+    let end_x_index = lines[end.y].byte_index_from_grapheme_index(end.x);
+    let line_end = lines[end.y][end_x_index..].to_string();
+    lines[start.y].truncate(start_x_index);
+    lines[start.y].push_str(line_end.as_str());
+    lines.drain(start.y + 1..=end.y);
+}
+
+// Returns the position at the end of inserted text
+fn insert_text(lines: &mut Vec<String>, pos: &Pos<usize>, text: &str) -> Pos<usize> {
+    let x_index = lines[pos.y].byte_index_from_grapheme_index(pos.x);
+    let line_end = lines[pos.y].split_off(x_index);
+    let mut parts = text.split('\n');
+    lines[pos.y].push_str(parts.next().unwrap_or(""));
+    let mut y = pos.y;
+    for part in parts {
+        y += 1;
+        lines.insert(y, part.to_string());
+    }
+    let end = Pos { x: if y == pos.y { pos.x + text.grapheme_count() } else { lines[y].grapheme_count() }, y };
+    lines[y].push_str(line_end.as_str());
+    end
+}
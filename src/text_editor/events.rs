@@ -0,0 +1,111 @@
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EditorEvent {
+    // Text between start and end (grapheme positions before the edit) has been replaced by text.
+    // Applying edits in order on a copy of the content gives the editor content.
+    Edited { start: Pos<usize>, end: Pos<usize>, text: String },
+    CursorChanged { cursor: Pos<usize>, selection: Option<(Pos<usize>, Pos<usize>)> },
+    Saved,
+    DirtyChanged(bool),
+    // Scroll offset in pixels
+    Scrolled(Pos<f32>),
+}
+
+// Events of all views on a buffer (e.g: split view), lent along with it.
+#[derive(Default)]
+pub(crate) struct EventQueue {
+    events: Vec<EditorEvent>,
+    // Dirty state reported by the last event
+    is_dirty: bool,
+}
+
+// View state reported by the last events, changes are detected by comparing against it once per frame.
+#[derive(Default)]
+pub(crate) struct ChangeEvents {
+    cursor: Pos<usize>,
+    selection: Option<(Pos<usize>, Pos<usize>)>,
+    scroll_offset: Pos<f32>,
+}
+
+impl TextEditor {
+    // Events since last call, when emit_events option is enabled. Views on the same buffer share their events.
+    pub fn take_events(&mut self) -> Vec<EditorEvent> {
+        std::mem::take(&mut self.event_queue.events)
+    }
+
+    // Text between start and end is replaced by text ending at new_end.
+    pub(crate) fn on_text_replaced(&mut self, start: &Pos<usize>, end: &Pos<usize>, new_end: &Pos<usize>, text: &str) {
+        self.shift_protected_ranges(end, new_end);
+        self.record_view_edit(start, end, new_end);
+        self.on_mapped_text_replaced(start, end, text);
+        if self.options.emit_events {
+            self.event_queue.events.push(EditorEvent::Edited { start: start.clone(), end: end.clone(), text: text.to_string() });
+        }
+    }
+
+    // Lines from start_index to end_index (inclusive) are replaced by new_lines, called before lines are changed.
+    pub(crate) fn on_lines_replaced(&mut self, start_index: usize, end_index: usize, new_lines: &[String]) {
+        self.shift_protected_lines(start_index, end_index, new_lines);
+        if self.view_edits.is_some() {
+            let (start, end, new_end) = self.lines_replacement_bounds(start_index, end_index, new_lines);
+            self.record_view_edit(&start, &end, &new_end);
+        }
+        if !self.options.emit_events && self.mapped_content.is_none() {
+            return;
+        }
+        let end = Pos { x: self.lines[end_index].grapheme_count(), y: end_index };
+        // removed lines take a line break with them
+        let (start, end) = if !new_lines.is_empty() {
+            (Pos { x: 0, y: start_index }, end)
+        } else if end_index + 1 < self.lines.len() {
+            (Pos { x: 0, y: start_index }, Pos { x: 0, y: end_index + 1 })
+        } else if start_index > 0 {
+            (Pos { x: self.lines[start_index - 1].grapheme_count(), y: start_index - 1 }, end)
+        } else {
+            (Pos { x: 0, y: 0 }, end)
+        };
+        let text = new_lines.join("\n");
+        self.on_mapped_text_replaced(&start, &end, &text);
+        if self.options.emit_events {
+            self.event_queue.events.push(EditorEvent::Edited { start, end, text });
+        }
+    }
+
+    pub(crate) fn on_saved(&mut self) {
+        if self.options.emit_events {
+            self.event_queue.events.push(EditorEvent::Saved);
+        }
+    }
+
+    // Cursor, selection, dirty state and scroll changes of the frame.
+    // Views on a shared buffer report their cursor and scroll only while active, events don't tell which view they come from.
+    pub(crate) fn update_change_events(&mut self) {
+        if !self.options.emit_events {
+            return;
+        }
+        let is_dirty = self.is_dirty();
+        if self.event_queue.is_dirty != is_dirty {
+            self.event_queue.is_dirty = is_dirty;
+            self.event_queue.events.push(EditorEvent::DirtyChanged(is_dirty));
+        }
+        if !self.has_keyboard_focus {
+            return;
+        }
+        let selection = if self.has_selection() {
+            Some((self.selection_start_index.clone().unwrap(), self.selection_end_index.clone().unwrap()))
+        } else {
+            None
+        };
+        if self.change_events.cursor != self.cursor_index || self.change_events.selection != selection {
+            self.change_events.cursor = self.cursor_index.clone();
+            self.change_events.selection = selection.clone();
+            self.event_queue.events.push(EditorEvent::CursorChanged { cursor: self.cursor_index.clone(), selection });
+        }
+        if self.change_events.scroll_offset != self.scroll_offset {
+            self.change_events.scroll_offset = self.scroll_offset.clone();
+            self.event_queue.events.push(EditorEvent::Scrolled(self.scroll_offset.clone()));
+        }
+    }
+}
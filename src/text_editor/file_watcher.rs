@@ -0,0 +1,97 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use eframe::egui;
+use eframe::egui::Ui;
+use crate::text_editor::TextEditor;
+
+const WatchPollInterval: Duration = Duration::from_millis(500);
+
+// Polls modification time and size of a file in a background thread, until dropped.
+pub struct FileWatcher {
+    receiver: Receiver<()>,
+    is_stopped: Arc<AtomicBool>,
+}
+
+impl FileWatcher {
+    // Context is repainted when file changes, so the change is handled without waiting for user input.
+    pub fn new(path: &Path, ctx: egui::Context) -> Self {
+        let (sender, receiver) = channel();
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let is_thread_stopped = is_stopped.clone();
+        let path: PathBuf = path.to_path_buf();
+        thread::spawn(move || {
+            let mut last_modification = Self::modification(&path);
+            while !is_thread_stopped.load(Ordering::Relaxed) {
+                thread::sleep(WatchPollInterval);
+                let modification = Self::modification(&path);
+                if modification != last_modification {
+                    last_modification = modification;
+                    if sender.send(()).is_err() {
+                        break;
+                    }
+                    ctx.request_repaint();
+                }
+            }
+        });
+        Self { receiver, is_stopped }
+    }
+
+    // True when file changed since last call
+    pub fn has_changed(&self) -> bool {
+        let mut has_changed = false;
+        while self.receiver.try_recv().is_ok() {
+            has_changed = true;
+        }
+        has_changed
+    }
+
+    fn modification(path: &Path) -> Option<(SystemTime, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+impl TextEditor {
+    // Replaces content with text read from disk, as a single history entry. Content is then considered saved.
+    pub fn reload(&mut self, ui: &Ui, text: &str) {
+        self.replace_content(ui, text);
+        self.mark_saved();
+    }
+
+    // Replaces content as a single history entry. Only changed lines are replaced, cursor and scroll stay where they are.
+    // Content is replaced even when read only or protected, protected ranges follow unchanged lines.
+    pub(crate) fn replace_content(&mut self, ui: &Ui, text: &str) {
+        let new_lines = text.split("\n").map(|s| s.to_string()).collect::<Vec<String>>();
+        let prefix = self.lines.iter().zip(new_lines.iter()).take_while(|(line, new_line)| line == new_line).count();
+        let max_suffix = self.lines.len().min(new_lines.len()) - prefix;
+        let mut suffix = self.lines.iter().rev().zip(new_lines.iter().rev()).take(max_suffix).take_while(|(line, new_line)| line == new_line).count();
+        if prefix == self.lines.len() && prefix == new_lines.len() {
+            return;
+        }
+        let mut start = prefix;
+        // history actions refer to an inclusive line range, replaced range contains at least one line
+        if prefix + suffix == self.lines.len() {
+            if start > 0 {
+                start -= 1;
+            } else {
+                suffix -= 1;
+            }
+        }
+        let end = self.lines.len() - suffix - 1;
+        let replacement = new_lines[start..new_lines.len() - suffix].to_vec();
+        self.on_lines_replaced(start, end, &replacement);
+        self.splice_lines(ui, start, end, replacement);
+        self.sanitize_view_state();
+    }
+}
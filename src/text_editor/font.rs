@@ -0,0 +1,179 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use glow_glyph::ab_glyph::{Font, FontArc};
+use glow_glyph::FontId;
+use crate::text_editor::TextEditor;
+
+const DefaultFont: &[u8] = include_bytes!("../Inconsolata-Regular.ttf");
+
+#[derive(Debug, Clone)]
+pub enum FontSource {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+}
+
+impl FontSource {
+    pub fn load(&self) -> io::Result<FontArc> {
+        let bytes = match self {
+            FontSource::Path(path) => fs::read(path)?,
+            FontSource::Bytes(bytes) => bytes.clone(),
+        };
+        FontArc::try_from_vec(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+}
+
+// When regular font is not set, the embedded Inconsolata font is used.
+// Fallback fonts are used, in order, for characters missing from the regular font (e.g: cjk, emoji).
+#[derive(Debug, Clone, Default)]
+pub struct FontOptions {
+    pub regular: Option<FontSource>,
+    pub bold: Option<FontSource>,
+    pub italic: Option<FontSource>,
+    pub fallbacks: Vec<FontSource>,
+}
+
+// Font which can't be loaded, the regular font is used instead.
+#[derive(Debug, Clone)]
+pub struct FontLoadError {
+    pub source: FontSource,
+    pub message: String,
+}
+
+// Fonts as registered in glyph brushes: index in fonts is the brush FontId.
+#[derive(Clone)]
+pub(crate) struct Fonts {
+    pub(crate) fonts: Vec<FontArc>,
+    bold_id: FontId,
+    italic_id: FontId,
+    fallback_ids: Vec<FontId>,
+    load_errors: Vec<FontLoadError>,
+}
+
+impl Fonts {
+    pub(crate) fn load(options: &FontOptions) -> Self {
+        let mut load_errors = vec![];
+        let mut load = |source: &FontSource| match source.load() {
+            Ok(font) => Some(font),
+            Err(error) => {
+                load_errors.push(FontLoadError { source: source.clone(), message: error.to_string() });
+                None
+            }
+        };
+        let regular = options.regular.as_ref().and_then(&mut load)
+            .unwrap_or_else(|| FontArc::try_from_slice(DefaultFont).unwrap());
+        let bold = options.bold.as_ref().and_then(&mut load);
+        let italic = options.italic.as_ref().and_then(&mut load);
+        let fallbacks = options.fallbacks.iter().filter_map(&mut load).collect::<Vec<FontArc>>();
+        let mut fonts = vec![regular];
+        let mut register = |maybe_font: Option<FontArc>| -> Option<FontId> {
+            maybe_font.map(|font| {
+                fonts.push(font);
+                FontId(fonts.len() - 1)
+            })
+        };
+        let bold_id = register(bold).unwrap_or(FontId(0));
+        let italic_id = register(italic).unwrap_or(FontId(0));
+        let fallback_ids = fallbacks.into_iter().filter_map(|font| register(Some(font))).collect();
+        Self { fonts, bold_id, italic_id, fallback_ids, load_errors }
+    }
+
+    pub(crate) fn regular(&self) -> &FontArc {
+        &self.fonts[0]
+    }
+
+    pub(crate) fn font_id(&self, style: FontStyle) -> FontId {
+        match style {
+            FontStyle::Regular => FontId(0),
+            FontStyle::Bold => self.bold_id,
+            FontStyle::Italic => self.italic_id,
+        }
+    }
+
+    // Split text into runs rendered with the same font: the styled font when it has a glyph for the character,
+    // otherwise the first font of the fallback chain having one.
+    pub(crate) fn runs<'a>(&self, text: &'a str, style: FontStyle) -> Vec<(&'a str, FontId)> {
+        let style_id = self.font_id(style);
+        if self.fallback_ids.is_empty() || text.is_ascii() {
+            return vec![(text, style_id)];
+        }
+        let mut runs: Vec<(&'a str, FontId)> = vec![];
+        let mut run_start = 0;
+        let mut run_font_id = style_id;
+        for (i, c) in text.char_indices() {
+            let font_id = self.font_id_for_char(c, style_id);
+            if font_id != run_font_id && i > run_start {
+                runs.push((&text[run_start..i], run_font_id));
+                run_start = i;
+            }
+            run_font_id = font_id;
+        }
+        runs.push((&text[run_start..], run_font_id));
+        runs
+    }
+
+    fn font_id_for_char(&self, c: char, style_id: FontId) -> FontId {
+        if c.is_control() || self.fonts[style_id.0].glyph_id(c).0 != 0 {
+            return style_id;
+        }
+        self.fallback_ids.iter().find(|id| self.fonts[id.0].glyph_id(c).0 != 0).copied().unwrap_or(style_id)
+    }
+}
+
+const ZoomStep: f32 = 0.1;
+const MinZoom: f32 = 0.5;
+const MaxZoom: f32 = 4.0;
+
+// Fonts and zoom can be changed at runtime: metrics and glyph brushes are recomputed, the widget is kept.
+pub trait FontSettings {
+    fn set_fonts(&mut self, font_options: FontOptions);
+    // Fonts of the last set_fonts (or of the options at creation) which can't be loaded
+    fn font_load_errors(&self) -> &[FontLoadError];
+    fn zoom(&self) -> f32;
+    fn set_zoom(&mut self, zoom: f32);
+    fn zoom_in(&mut self);
+    fn zoom_out(&mut self);
+}
+
+impl FontSettings for TextEditor {
+    fn set_fonts(&mut self, font_options: FontOptions) {
+        self.fonts = Fonts::load(&font_options);
+        self.options.font = font_options;
+        self.rebuild_glyph_brushes();
+        self.update_font_metrics();
+    }
+
+    fn font_load_errors(&self) -> &[FontLoadError] {
+        &self.fonts.load_errors
+    }
+
+    fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    fn set_zoom(&mut self, zoom: f32) {
+        let zoom = zoom.max(MinZoom).min(MaxZoom);
+        if zoom == self.zoom {
+            return;
+        }
+        self.zoom = zoom;
+        // Brushes cache glyphs rasterized at previous scale, they are rebuilt to start from a clean cache.
+        self.rebuild_glyph_brushes();
+        self.update_font_metrics();
+    }
+
+    fn zoom_in(&mut self) {
+        self.set_zoom(self.zoom + ZoomStep);
+    }
+
+    fn zoom_out(&mut self) {
+        self.set_zoom(self.zoom - ZoomStep);
+    }
+}
@@ -0,0 +1,345 @@
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use eframe::egui;
+use eframe::egui::{Id, Key, Pos2, Rect, Rounding, Sense, Ui};
+use eframe::epaint::{RectShape, Shape};
+use crate::text_editor::TextEditor;
+use crate::text_editor::diff::{diff_hunks, Hunk};
+
+const GitMarkerWidth: f32 = 3.0;
+
+// Version of the file lines are compared to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GitBase {
+    Index,
+    Head,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GitChangeKind {
+    Added,
+    Modified,
+    // Lines removed after the marker line
+    Deleted,
+}
+
+// Hunks between the git version of the file (left) and lines (right).
+#[derive(Default)]
+pub(crate) struct GitChanges {
+    base_lines: Option<Vec<String>>,
+    hunks: Vec<Hunk>,
+    // None forces an update
+    edit_signature: Option<(usize, usize, usize)>,
+    opened_hunk: Option<Hunk>,
+}
+
+impl GitChanges {
+    pub(crate) fn invalidate(&mut self) {
+        self.edit_signature = None;
+    }
+
+    fn set_base(&mut self, text: Option<String>) {
+        self.base_lines = text.map(|text| text.split("\n").map(|s| s.to_string()).collect());
+        self.hunks.clear();
+        self.opened_hunk = None;
+        self.invalidate();
+    }
+
+    // Diff is computed again only when lines have been edited since last update.
+    fn update(&mut self, lines: &[String], edit_signature: (usize, usize, usize)) {
+        let base_lines = match self.base_lines.as_ref() {
+            Some(base_lines) => base_lines,
+            None => return,
+        };
+        if self.edit_signature == Some(edit_signature) {
+            return;
+        }
+        self.hunks = diff_hunks(base_lines, lines);
+        self.edit_signature = Some(edit_signature);
+        if let Some(opened_hunk) = self.opened_hunk.as_ref() {
+            if !self.hunks.contains(opened_hunk) {
+                self.opened_hunk = None;
+            }
+        }
+    }
+
+    fn line_changes(&self) -> Vec<(GitChangeKind, std::ops::Range<usize>)> {
+        self.hunks.iter().map(|hunk| (change_kind(hunk), hunk.right.clone())).collect()
+    }
+
+    fn original_lines(&self, hunk: &Hunk) -> Vec<String> {
+        self.base_lines.as_ref().map_or(vec![], |base_lines| base_lines[hunk.left.clone()].to_vec())
+    }
+
+    // Lines range (inclusive) to replace, and their replacement, restoring the original lines of the hunk.
+    fn revert(&self, hunk: &Hunk, lines: &[String]) -> (usize, usize, Vec<String>) {
+        let original_lines = self.original_lines(hunk);
+        if !hunk.right.is_empty() {
+            (hunk.right.start, hunk.right.end - 1, original_lines)
+        } else if hunk.right.start > 0 {
+            // removed lines are inserted back after the line preceding them
+            let previous_line_index = hunk.right.start - 1;
+            let mut new_lines = vec![lines[previous_line_index].clone()];
+            new_lines.extend(original_lines);
+            (previous_line_index, previous_line_index, new_lines)
+        } else {
+            let mut new_lines = original_lines;
+            new_lines.push(lines[0].clone());
+            (0, 0, new_lines)
+        }
+    }
+}
+
+// Content of the file in the index or at HEAD, read with git from the file directory.
+// Returns None when file is not in a git work tree, an empty text when file is not tracked.
+pub fn read_git_base(path: &Path, base: GitBase) -> io::Result<Option<String>> {
+    let directory = path.parent().filter(|directory| !directory.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let is_inside_work_tree = Command::new("git").arg("-C").arg(directory).args(["rev-parse", "--is-inside-work-tree"])
+        .output()?;
+    if !is_inside_work_tree.status.success() {
+        return Ok(None);
+    }
+    // "./" makes the path relative to the directory instead of the repository root
+    let revision = match base {
+        GitBase::Index => ":./",
+        GitBase::Head => "HEAD:./",
+    };
+    let show = Command::new("git").arg("-C").arg(directory).arg("show").arg(format!("{}{}", revision, file_name.to_string_lossy()))
+        .output()?;
+    if !show.status.success() {
+        return Ok(Some(String::new()));
+    }
+    Ok(Some(String::from_utf8_lossy(&show.stdout).to_string()))
+}
+
+fn change_kind(hunk: &Hunk) -> GitChangeKind {
+    if hunk.left.is_empty() {
+        GitChangeKind::Added
+    } else if hunk.right.is_empty() {
+        GitChangeKind::Deleted
+    } else {
+        GitChangeKind::Modified
+    }
+}
+
+// Added, modified and deleted lines markers in the gutter, compared to the git index or HEAD. Clicking a marker shows
+// the original lines, which can be restored.
+pub trait GitGutter {
+    fn load_git_base(&mut self, path: &Path, base: GitBase) -> io::Result<()>;
+    // None removes markers
+    fn set_git_base(&mut self, text: Option<String>);
+    // Line ranges of each change, deleted changes are an empty range before the next line.
+    fn git_line_changes(&self) -> Vec<(GitChangeKind, std::ops::Range<usize>)>;
+}
+
+impl GitGutter for TextEditor {
+    fn load_git_base(&mut self, path: &Path, base: GitBase) -> io::Result<()> {
+        let text = read_git_base(path, base)?;
+        self.set_git_base(text);
+        Ok(())
+    }
+
+    fn set_git_base(&mut self, text: Option<String>) {
+        self.git_changes.set_base(text);
+    }
+
+    fn git_line_changes(&self) -> Vec<(GitChangeKind, std::ops::Range<usize>)> {
+        self.git_changes.line_changes()
+    }
+}
+
+impl TextEditor {
+    pub(crate) fn git_marker_width(&self) -> f32 {
+        if self.git_changes.base_lines.is_some() { GitMarkerWidth } else { 0.0 }
+    }
+
+    pub(crate) fn update_git_changes(&mut self) {
+        let edit_signature = self.edit_signature();
+        self.git_changes.update(&self.lines, edit_signature);
+    }
+
+    // Line on which the marker of the hunk is displayed
+    fn git_marker_line(&self, hunk: &Hunk) -> usize {
+        hunk.right.start.min(self.lines.len().saturating_sub(1))
+    }
+
+    pub(crate) fn git_marker_shapes(&self, gutter_rect: Rect, first_line_index: usize, last_line_index: usize) -> Vec<Shape> {
+        let theme = &self.options.theme;
+        let x = gutter_rect.max.x - GitMarkerWidth;
+        let row_top = |line_index: usize| gutter_rect.min.y + self.index_to_y_in_virtual_scroll(line_index, first_line_index);
+        let mut shapes = vec![];
+        for hunk in self.git_changes.hunks.iter() {
+            let (top, bottom, fill) = match change_kind(hunk) {
+                GitChangeKind::Deleted => {
+                    let line_index = self.git_marker_line(hunk);
+                    if line_index < first_line_index || line_index >= last_line_index {
+                        continue;
+                    }
+                    // removed lines were between marker line and the previous one, or after the last line
+                    let y = if hunk.right.start < self.lines.len() { row_top(line_index) } else { row_top(line_index) + self.line_height };
+                    (y - GitMarkerWidth, y + GitMarkerWidth, theme.git_deleted)
+                }
+                kind => {
+                    if hunk.right.end <= first_line_index || hunk.right.start >= last_line_index {
+                        continue;
+                    }
+                    let fill = if kind == GitChangeKind::Added { theme.git_added } else { theme.git_modified };
+                    (row_top(hunk.right.start.max(first_line_index)), row_top(hunk.right.end.min(last_line_index)), fill)
+                }
+            };
+            // rows of pinned scope headers don't display their line markers
+            let top = top.max(gutter_rect.min.y + self.sticky_lines.len() as f32 * self.line_height);
+            if bottom <= top {
+                continue;
+            }
+            shapes.push(Shape::Rect(RectShape {
+                rect: Rect { min: Pos2 { x, y: top }, max: Pos2 { x: gutter_rect.max.x, y: bottom } },
+                rounding: Rounding::ZERO,
+                fill,
+                stroke: Default::default(),
+                fill_texture_id: Default::default(),
+                uv: Rect::ZERO,
+            }));
+        }
+        shapes
+    }
+
+    // Clicking a marker opens a popup with the original lines of its hunk.
+    pub(crate) fn git_gutter_interaction(&mut self, ui: &mut Ui, gutter_rect: Rect, first_line_index: usize) {
+        if self.git_changes.hunks.is_empty() {
+            return;
+        }
+        let response = ui.interact(gutter_rect, ui.id().with("git_gutter"), Sense::click());
+        let maybe_pointer = response.interact_pointer_pos().filter(|_| response.clicked());
+        if let Some(pointer) = maybe_pointer {
+            let line_index = first_line_index + ((pointer.y - gutter_rect.min.y) / self.line_height) as usize;
+            let maybe_hunk = self.git_changes.hunks.iter()
+                .find(|hunk| hunk.right.contains(&line_index) || self.git_marker_line(hunk) == line_index).cloned();
+            self.git_changes.opened_hunk = maybe_hunk;
+        }
+    }
+
+    pub(crate) fn git_hunk_popup(&mut self, ui: &mut Ui, gutter_rect: Rect, first_line_index: usize) {
+        let hunk = match self.git_changes.opened_hunk.clone() {
+            Some(hunk) => hunk,
+            None => return,
+        };
+        let line_index = self.git_marker_line(&hunk);
+        if line_index < first_line_index || ui.input(|input| input.key_pressed(Key::Escape)) {
+            self.git_changes.opened_hunk = None;
+            return;
+        }
+        let original_lines = self.git_changes.original_lines(&hunk);
+        let position = Pos2 { x: gutter_rect.max.x, y: gutter_rect.min.y + self.index_to_y_in_virtual_scroll(line_index, first_line_index) + self.line_height };
+        let mut is_revert_clicked = false;
+        let mut is_close_clicked = false;
+        egui::Area::new(Id::new("git_hunk_popup").with(ui.id())).fixed_pos(position).order(egui::Order::Foreground).show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                if original_lines.is_empty() {
+                    ui.label("Added lines");
+                } else {
+                    ui.label(egui::RichText::new(original_lines.join("\n")).monospace());
+                }
+                ui.horizontal(|ui| {
+                    is_revert_clicked = ui.button("Revert").clicked();
+                    is_close_clicked = ui.button("Close").clicked();
+                });
+            });
+        });
+        if is_revert_clicked {
+            self.revert_git_hunk(ui, &hunk);
+        }
+        if is_revert_clicked || is_close_clicked {
+            self.git_changes.opened_hunk = None;
+        }
+    }
+
+    // Restores original lines of the hunk, as a single history entry.
+    fn revert_git_hunk(&mut self, ui: &Ui, hunk: &Hunk) {
+        let (start_index, end_index, new_lines) = self.git_changes.revert(hunk, &self.lines);
+        self.replace_lines(ui, start_index, end_index, new_lines);
+        self.sanitize_cursor_position();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::text_editor::test_support::{git, lines, temp_repository};
+    use super::*;
+
+    fn changes(base: &str, text: &str) -> GitChanges {
+        let mut git_changes = GitChanges::default();
+        git_changes.set_base(Some(base.to_string()));
+        git_changes.update(&lines(text), (0, 0, 0));
+        git_changes
+    }
+
+    #[test]
+    fn read_git_base_from_index_and_head() {
+        let repository = temp_repository("git-base");
+        let directory = repository.path();
+        let path = directory.join("file.txt");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        git(directory, &["add", "file.txt"]);
+        git(directory, &["commit", "-q", "-m", "initial"]);
+        fs::write(&path, "a\nB\nc\n").unwrap();
+        git(directory, &["add", "file.txt"]);
+        fs::write(&path, "a\nB\nc\nd\n").unwrap();
+
+        assert_eq!(read_git_base(&path, GitBase::Head).unwrap(), Some("a\nb\nc\n".to_string()));
+        assert_eq!(read_git_base(&path, GitBase::Index).unwrap(), Some("a\nB\nc\n".to_string()));
+        // untracked file is compared to an empty text
+        let untracked_path = directory.join("untracked.txt");
+        fs::write(&untracked_path, "x").unwrap();
+        assert_eq!(read_git_base(&untracked_path, GitBase::Head).unwrap(), Some(String::new()));
+    }
+
+    #[test]
+    fn line_changes_of_edited_file() {
+        let repository = temp_repository("git-changes");
+        let path = repository.path().join("file.txt");
+        let committed = "one\ntwo\nthree\nfour\nfive";
+        fs::write(&path, committed).unwrap();
+        git(repository.path(), &["add", "file.txt"]);
+        git(repository.path(), &["commit", "-q", "-m", "initial"]);
+        let base = read_git_base(&path, GitBase::Head).unwrap().unwrap();
+
+        // two is modified, three is removed, six is added
+        let edited = "one\nTWO\nfour\nfive\nsix";
+        let git_changes = changes(&base, edited);
+        assert_eq!(git_changes.line_changes(), vec![(GitChangeKind::Modified, 1..2), (GitChangeKind::Added, 4..5)]);
+        let git_changes = changes(&base, "one\nfour\nfive");
+        assert_eq!(git_changes.line_changes(), vec![(GitChangeKind::Deleted, 1..1)]);
+        let git_changes = changes(&base, "two\nthree\nfour\nfive");
+        assert_eq!(git_changes.line_changes(), vec![(GitChangeKind::Deleted, 0..0)]);
+        assert_eq!(changes(&base, committed).line_changes(), vec![]);
+    }
+
+    #[test]
+    fn revert_restores_original_lines() {
+        let base = "one\ntwo\nthree\nfour\nfive";
+        for edited in ["one\nTWO\nfour\nfive\nsix", "one\nfour\nfive", "two\nthree\nfour\nfive", "zero\none\ntwo\nthree\nfour\nfive", "one\ntwo\nthree"] {
+            let mut current = lines(edited);
+            // hunks are reverted from the last one so line indexes of the others stay valid
+            let git_changes = changes(base, edited);
+            for hunk in git_changes.hunks.iter().rev() {
+                let (start_index, end_index, new_lines) = git_changes.revert(hunk, &current);
+                current.splice(start_index..=end_index, new_lines);
+            }
+            assert_eq!(current, lines(base), "{:?}", edited);
+        }
+    }
+
+    #[test]
+    fn changes_are_computed_again_when_edit_signature_changes() {
+        let mut git_changes = changes("a\nb", "a\nb");
+        assert_eq!(git_changes.line_changes(), vec![]);
+        git_changes.update(&lines("a\nB"), (0, 0, 0));
+        assert_eq!(git_changes.line_changes(), vec![]);
+        git_changes.update(&lines("a\nB"), (1, 0, 2));
+        assert_eq!(git_changes.line_changes(), vec![(GitChangeKind::Modified, 1..2)]);
+    }
+}
@@ -0,0 +1,68 @@
+use std::borrow::Cow;
+use eframe::egui::{Color32, Pos2, Rect, Stroke, Ui};
+use eframe::egui::output::IMEOutput;
+use eframe::epaint::Shape;
+use crate::text_editor::TextEditor;
+use crate::text_editor::unicode::UnicodeLine;
+
+// Input method composition (e.g: japanese, chinese): preedit text is displayed at the cursor position,
+// underlined, without being part of the buffer. It is inserted only when the composition is committed.
+pub(crate) trait ImeComposition {
+    fn is_composing(&self) -> bool;
+    fn on_composition_update(&mut self, text: &str);
+    fn on_composition_end(&mut self, ui: &Ui, text: &str);
+    fn line_with_preedit<'a>(&self, line: &'a str, line_index: usize) -> Cow<'a, str>;
+    fn preedit_underline_shape(&self, first_line_index: usize) -> Option<Shape>;
+    fn update_ime_output(&self, ui: &Ui, first_line_index: usize);
+}
+
+impl ImeComposition for TextEditor {
+    fn is_composing(&self) -> bool {
+        self.ime_preedit.is_some()
+    }
+
+    fn on_composition_update(&mut self, text: &str) {
+        if text.is_empty() {
+            self.ime_preedit = None;
+        } else {
+            self.ime_preedit = Some(text.to_string());
+        }
+    }
+
+    fn on_composition_end(&mut self, ui: &Ui, text: &str) {
+        self.ime_preedit = None;
+        if !text.is_empty() {
+            self.insert_typed_text(ui, text);
+        }
+    }
+
+    fn line_with_preedit<'a>(&self, line: &'a str, line_index: usize) -> Cow<'a, str> {
+        if self.ime_preedit.is_none() || line_index != self.cursor_index.y {
+            return Cow::Borrowed(line);
+        }
+        let x_index = line.byte_index_from_grapheme_index(self.cursor_index.x);
+        let mut line = line.to_string();
+        line.insert_str(x_index, self.ime_preedit.as_ref().unwrap());
+        Cow::Owned(line)
+    }
+
+    fn preedit_underline_shape(&self, first_line_index: usize) -> Option<Shape> {
+        if self.ime_preedit.is_none() || self.cursor_index.y < first_line_index {
+            return None;
+        }
+        let y = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.cursor_index.y, first_line_index) + self.line_height - 1.0;
+        let width = self.ime_preedit.as_ref().unwrap().columns_count() as f32 * self.char_width;
+        Some(Shape::line_segment([Pos2 { x: self.cursor_pos.x, y }, Pos2 { x: self.cursor_pos.x + width, y }], Stroke::new(1.0, Color32::BLACK)))
+    }
+
+    // Tells the platform that text input is expected, and where to position the candidate window.
+    fn update_ime_output(&self, ui: &Ui, first_line_index: usize) {
+        let preedit_width = self.ime_preedit.as_ref().map_or(0, |preedit| preedit.columns_count()) as f32 * self.char_width;
+        let y = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.cursor_index.y.max(first_line_index), first_line_index);
+        let cursor_rect = Rect {
+            min: Pos2 { x: self.cursor_pos.x + preedit_width, y },
+            max: Pos2 { x: self.cursor_pos.x + preedit_width + 2.0, y: y + self.line_height },
+        };
+        ui.output_mut(|output| output.ime = Some(IMEOutput { rect: self.text_editor_viewport, cursor_rect }));
+    }
+}
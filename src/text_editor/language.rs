@@ -0,0 +1,46 @@
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct Language {
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub line_comment: Option<String>,
+    pub block_comment: Option<(String, String)>,
+}
+
+impl Language {
+    pub fn new(name: &str, extensions: &[&str], line_comment: Option<&str>, block_comment: Option<(&str, &str)>) -> Self {
+        Self {
+            name: name.to_string(),
+            extensions: extensions.iter().map(|extension| extension.to_string()).collect(),
+            line_comment: line_comment.map(|token| token.to_string()),
+            block_comment: block_comment.map(|(open, close)| (open.to_string(), close.to_string())),
+        }
+    }
+
+    pub fn builtin() -> Vec<Language> {
+        vec![
+            Language::new("rust", &["rs"], Some("//"), Some(("/*", "*/"))),
+            Language::new("c", &["c", "h", "cpp", "hpp", "cc"], Some("//"), Some(("/*", "*/"))),
+            Language::new("java", &["java", "kt", "scala", "groovy"], Some("//"), Some(("/*", "*/"))),
+            Language::new("javascript", &["js", "ts", "jsx", "tsx", "go", "swift", "cs"], Some("//"), Some(("/*", "*/"))),
+            Language::new("css", &["css"], None, Some(("/*", "*/"))),
+            Language::new("python", &["py"], Some("#"), Some(("\"\"\"", "\"\"\""))),
+            Language::new("shell", &["sh", "bash", "zsh", "toml", "yaml", "yml", "conf", "properties"], Some("#"), None),
+            Language::new("sql", &["sql"], Some("--"), Some(("/*", "*/"))),
+            Language::new("lua", &["lua"], Some("--"), Some(("--[[", "]]"))),
+            Language::new("html", &["html", "htm", "xml", "svg", "md"], None, Some(("<!--", "-->"))),
+        ]
+    }
+
+    pub fn from_path(path: &Path) -> Option<Language> {
+        let extension = path.extension()?.to_str()?;
+        Self::builtin().into_iter().find(|language| language.extensions.iter().any(|e| e == extension))
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language::new("rust", &["rs"], Some("//"), Some(("/*", "*/")))
+    }
+}
@@ -0,0 +1,117 @@
+use eframe::egui::Ui;
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+pub(crate) trait LineEdit {
+    fn duplicate_lines(&mut self, ui: &Ui);
+    fn move_lines(&mut self, ui: &Ui, up: bool);
+    fn delete_lines(&mut self, ui: &Ui);
+    fn join_lines(&mut self, ui: &Ui);
+}
+
+impl LineEdit for TextEditor {
+    fn duplicate_lines(&mut self, ui: &Ui) {
+        if self.has_selection() {
+            // Selected text is duplicated right after the selection, the selection moves to the copy.
+            let selection_end_index = self.selection_end_index.clone().unwrap();
+            let text = self.selected_text();
+            let parts = text.split('\n').collect::<Vec<&str>>();
+            let line = &self.lines[selection_end_index.y];
+            let end_x_index = line.byte_index_from_grapheme_index(selection_end_index.x);
+            let mut new_lines = parts.iter().map(|part| part.to_string()).collect::<Vec<String>>();
+            new_lines[0] = format!("{}{}", &line[0..end_x_index], new_lines[0]);
+            new_lines.last_mut().unwrap().push_str(&line[end_x_index..]);
+
+            let new_selection_end_index = if parts.len() == 1 {
+                Pos { x: selection_end_index.x + parts[0].grapheme_count(), y: selection_end_index.y }
+            } else {
+                Pos { x: parts.last().unwrap().grapheme_count(), y: selection_end_index.y + parts.len() - 1 }
+            };
+            if !self.replace_lines(ui, selection_end_index.y, selection_end_index.y, new_lines) {
+                return;
+            }
+            self.set_cursor_y(new_selection_end_index.y);
+            self.set_cursor_x(new_selection_end_index.x);
+            self.select_range(selection_end_index, new_selection_end_index);
+        } else {
+            let line = self.lines[self.cursor_index.y].clone();
+            if !self.replace_lines(ui, self.cursor_index.y, self.cursor_index.y, vec![line.clone(), line]) {
+                return;
+            }
+            self.set_cursor_y(self.cursor_index.y + 1);
+        }
+        self.has_pressed_arrow_key = true;
+    }
+
+    fn move_lines(&mut self, ui: &Ui, up: bool) {
+        let (start_y, end_y) = self.selected_line_range();
+        if (up && start_y == 0) || (!up && end_y + 1 >= self.lines.len()) {
+            return;
+        }
+        let selection = if self.has_selection() {
+            Some((self.selection_start_index.clone().unwrap(), self.selection_end_index.clone().unwrap()))
+        } else {
+            None
+        };
+        let moved_lines = self.lines[start_y..=end_y].to_vec();
+        let is_moved = if up {
+            let mut new_lines = moved_lines;
+            new_lines.push(self.lines[start_y - 1].clone());
+            self.replace_lines(ui, start_y - 1, end_y, new_lines)
+        } else {
+            let mut new_lines = vec![self.lines[end_y + 1].clone()];
+            new_lines.extend(moved_lines);
+            self.replace_lines(ui, start_y, end_y + 1, new_lines)
+        };
+        if !is_moved {
+            return;
+        }
+        let shift_y = |y: usize| if up { y - 1 } else { y + 1 };
+        self.set_cursor_y(shift_y(self.cursor_index.y));
+        if let Some((selection_start_index, selection_end_index)) = selection {
+            self.select_range(Pos { x: selection_start_index.x, y: shift_y(selection_start_index.y) },
+                              Pos { x: selection_end_index.x, y: shift_y(selection_end_index.y) });
+        }
+        self.has_pressed_arrow_key = true;
+    }
+
+    fn delete_lines(&mut self, ui: &Ui) {
+        let (start_y, end_y) = self.selected_line_range();
+        if !self.replace_lines(ui, start_y, end_y, vec![]) {
+            return;
+        }
+        self.reset_selection();
+        self.set_cursor_y(start_y.min(self.lines.len() - 1));
+        // cursor y may be unchanged while its line is a different one now
+        self.sanitize_cursor_position();
+        self.has_pressed_arrow_key = true;
+    }
+
+    fn join_lines(&mut self, ui: &Ui) {
+        let (start_y, mut end_y) = self.selected_line_range();
+        if start_y == end_y {
+            end_y += 1;
+        }
+        if end_y >= self.lines.len() {
+            return;
+        }
+        let mut joined_line = self.lines[start_y].clone();
+        let mut junction_x = joined_line.grapheme_count();
+        for line in self.lines[start_y + 1..=end_y].iter() {
+            let line = line.trim_start();
+            let trimmed_len = joined_line.trim_end().len();
+            joined_line.truncate(trimmed_len);
+            if !joined_line.is_empty() && !line.is_empty() {
+                joined_line.push(' ');
+            }
+            junction_x = joined_line.grapheme_count();
+            joined_line.push_str(line);
+        }
+        if !self.replace_lines(ui, start_y, end_y, vec![joined_line]) {
+            return;
+        }
+        self.reset_selection();
+        self.set_cursor_y(start_y);
+        self.set_cursor_x(junction_x);
+    }
+}
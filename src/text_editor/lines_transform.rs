@@ -0,0 +1,155 @@
+use std::cmp::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+use eframe::egui::Ui;
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Lexical,
+    // Digits sequences are compared by their numeric value: "file2" < "file10"
+    Natural,
+    CaseInsensitive,
+    // Compare lines by their nth whitespace separated column, starting at 0
+    Column(usize),
+}
+
+// Commands working on lines touched by the selection, or the whole text when nothing is selected.
+pub trait LinesTransform {
+    fn sort_lines(&mut self, ui: &Ui, order: SortOrder);
+    fn reverse_lines(&mut self, ui: &Ui);
+    fn unique_lines(&mut self, ui: &Ui);
+    fn shuffle_lines(&mut self, ui: &Ui);
+    fn trim_trailing_whitespace(&mut self, ui: &Ui);
+}
+
+impl LinesTransform for TextEditor {
+    fn sort_lines(&mut self, ui: &Ui, order: SortOrder) {
+        self.transform_lines(ui, |lines| {
+            match order {
+                SortOrder::Lexical => lines.sort(),
+                SortOrder::Natural => lines.sort_by(|a, b| natural_cmp(a, b)),
+                SortOrder::CaseInsensitive => lines.sort_by_cached_key(|line| line.to_lowercase()),
+                SortOrder::Column(column) => lines.sort_by(|a, b| {
+                    let a_column = a.split_whitespace().nth(column).unwrap_or("");
+                    let b_column = b.split_whitespace().nth(column).unwrap_or("");
+                    natural_cmp(a_column, b_column)
+                }),
+            }
+        });
+    }
+
+    fn reverse_lines(&mut self, ui: &Ui) {
+        self.transform_lines(ui, |lines| lines.reverse());
+    }
+
+    fn unique_lines(&mut self, ui: &Ui) {
+        self.transform_lines(ui, |lines| {
+            let mut seen = std::collections::HashSet::new();
+            lines.retain(|line| seen.insert(line.clone()));
+        });
+    }
+
+    fn shuffle_lines(&mut self, ui: &Ui) {
+        let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(1) | 1;
+        self.transform_lines(ui, |lines| {
+            // Fisher-Yates with a xorshift generator, we don't need a good randomness here.
+            for i in (1..lines.len()).rev() {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                lines.swap(i, (seed % (i as u64 + 1)) as usize);
+            }
+        });
+    }
+
+    fn trim_trailing_whitespace(&mut self, ui: &Ui) {
+        self.transform_lines(ui, |lines| {
+            for line in lines.iter_mut() {
+                line.truncate(line.trim_end().len());
+            }
+        });
+    }
+}
+
+impl TextEditor {
+    fn transform_lines<F: FnOnce(&mut Vec<String>)>(&mut self, ui: &Ui, transform: F) {
+        let had_selection = self.has_selection();
+        let (start_y, end_y) = if had_selection {
+            self.selected_line_range()
+        } else {
+            (0, whole_text_last_line_index(&self.lines))
+        };
+        self.load_mapped_lines(start_y..end_y + 1);
+        let mut lines = self.lines[start_y..=end_y].to_vec();
+        transform(&mut lines);
+        if lines.as_slice() == &self.lines[start_y..=end_y] {
+            return;
+        }
+        let new_end_y = start_y + lines.len().max(1) - 1;
+        if !self.replace_lines(ui, start_y, end_y, lines) {
+            return;
+        }
+        self.sanitize_cursor_position();
+        if had_selection {
+            let end_x = self.lines[new_end_y].grapheme_count();
+            self.select_range(Pos { x: 0, y: start_y }, Pos { x: end_x, y: new_end_y });
+        }
+    }
+}
+
+// Empty last line following the final line break of a file is not a line of the text
+fn whole_text_last_line_index(lines: &[String]) -> usize {
+    if lines.len() > 1 && lines[lines.len() - 1].is_empty() { lines.len() - 2 } else { lines.len() - 1 }
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let mut a_number = String::new();
+                while let Some(c) = a_chars.next_if(|c| c.is_ascii_digit()) {
+                    a_number.push(c);
+                }
+                let mut b_number = String::new();
+                while let Some(c) = b_chars.next_if(|c| c.is_ascii_digit()) {
+                    b_number.push(c);
+                }
+                let a_number = a_number.trim_start_matches('0');
+                let b_number = b_number.trim_start_matches('0');
+                let ordering = a_number.len().cmp(&b_number.len()).then_with(|| a_number.cmp(b_number));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(a_char), Some(b_char)) => {
+                let ordering = a_char.cmp(b_char);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text_editor::test_support::lines;
+    use super::*;
+
+    #[test]
+    fn whole_text_excludes_final_line_break() {
+        assert_eq!(whole_text_last_line_index(&lines("b\na\n")), 1);
+        assert_eq!(whole_text_last_line_index(&lines("b\na")), 1);
+        assert_eq!(whole_text_last_line_index(&lines("b\n\n")), 1);
+        assert_eq!(whole_text_last_line_index(&lines("")), 0);
+        assert_eq!(whole_text_last_line_index(&lines("\n")), 0);
+    }
+}
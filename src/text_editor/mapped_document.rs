@@ -0,0 +1,537 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use eframe::egui::{Pos2, Rect, Ui};
+use memmap2::Mmap;
+use crate::text_editor::{Pos, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+// Line index is built by chunks, lines of indexed chunks can be served while the rest is indexed.
+const IndexChunkSize: usize = 16 * 1024 * 1024;
+// Indexing progress is polled at this interval
+const IndexingPollInterval: Duration = Duration::from_millis(100);
+const IndexingProgressHeight: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoadProgress {
+    pub indexed_bytes: usize,
+    pub total_bytes: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Original,
+    Added,
+}
+
+// Part of the document: a range of the mapped file or of the added text.
+#[derive(Debug, Clone)]
+struct Piece {
+    source: Source,
+    start: usize,
+    len: usize,
+    line_breaks: usize,
+}
+
+// Document content is the concatenation of pieces. Edits split and replace pieces, the mapped file is never copied.
+struct PieceTable {
+    pieces: Vec<Piece>,
+    // Append only
+    added: String,
+}
+
+// File mapped in memory instead of being read into lines. Line start offsets are indexed in a background thread and
+// lines are read on demand (e.g: first_line_index..last_line_Index of the viewport). Edits go through a piece table.
+// Mapped file must not be modified by another process while it is opened.
+pub struct MappedDocument {
+    mmap: Arc<Mmap>,
+    // Offset of each line start in the mapped file
+    line_starts: Arc<RwLock<Vec<usize>>>,
+    indexed_bytes: Arc<AtomicUsize>,
+    is_indexed: Arc<AtomicBool>,
+    // Created on first edit
+    piece_table: Option<PieceTable>,
+}
+
+impl MappedDocument {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // Safety: file is expected to not be modified while mapped, see MappedDocument documentation.
+        let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+        let line_starts = Arc::new(RwLock::new(vec![0]));
+        let indexed_bytes = Arc::new(AtomicUsize::new(0));
+        let is_indexed = Arc::new(AtomicBool::new(false));
+        let (thread_mmap, thread_line_starts, thread_indexed_bytes, thread_is_indexed) =
+            (mmap.clone(), line_starts.clone(), indexed_bytes.clone(), is_indexed.clone());
+        thread::spawn(move || {
+            for (chunk_index, chunk) in thread_mmap.chunks(IndexChunkSize).enumerate() {
+                let chunk_start = chunk_index * IndexChunkSize;
+                let chunk_line_starts = chunk.iter().enumerate()
+                    .filter(|(_, byte)| **byte == b'\n')
+                    .map(|(i, _)| chunk_start + i + 1).collect::<Vec<usize>>();
+                thread_line_starts.write().unwrap().extend(chunk_line_starts);
+                thread_indexed_bytes.store(chunk_start + chunk.len(), Ordering::Release);
+            }
+            thread_is_indexed.store(true, Ordering::Release);
+        });
+        Ok(Self { mmap, line_starts, indexed_bytes, is_indexed, piece_table: None })
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        LoadProgress { indexed_bytes: self.indexed_bytes.load(Ordering::Acquire), total_bytes: self.mmap.len() }
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.is_indexed.load(Ordering::Acquire)
+    }
+
+    // While indexing, only lines of the indexed part are counted.
+    pub fn lines_count(&self) -> usize {
+        match self.piece_table.as_ref() {
+            Some(piece_table) => 1 + piece_table.pieces.iter().map(|piece| piece.line_breaks).sum::<usize>(),
+            None if self.is_indexed() => self.line_starts.read().unwrap().len(),
+            // last indexed line may continue in the next chunk
+            None => self.line_starts.read().unwrap().len() - 1,
+        }
+    }
+
+    // Lines in range, without line breaks. Lines not yet indexed are not returned.
+    pub fn lines(&self, range: Range<usize>) -> Vec<String> {
+        let range = range.start.min(self.lines_count())..range.end.min(self.lines_count());
+        range.filter_map(|line_index| {
+            let start = self.line_start(line_index)?;
+            let end = self.line_start(line_index + 1).map_or(self.len(), |next_line_start| next_line_start - 1);
+            Some(String::from_utf8_lossy(&self.bytes(start..end)).to_string())
+        }).collect()
+    }
+
+    // Document offset of a line and grapheme index position
+    pub fn offset(&self, pos: &Pos<usize>) -> Option<usize> {
+        let line_start = self.line_start(pos.y)?;
+        let line = self.lines(pos.y..pos.y + 1).pop()?;
+        Some(line_start + line.byte_index_from_grapheme_index(pos.x))
+    }
+
+    // Edits are only possible once the file is indexed, returns false otherwise.
+    pub fn insert(&mut self, offset: usize, text: &str) -> bool {
+        if !self.ensure_piece_table() || offset > self.len() || text.is_empty() {
+            return false;
+        }
+        let piece_index = self.split_at(offset);
+        let piece_table = self.piece_table.as_mut().unwrap();
+        let piece = Piece { source: Source::Added, start: piece_table.added.len(), len: text.len(), line_breaks: bytecount::count(text.as_bytes(), b'\n') };
+        piece_table.added.push_str(text);
+        piece_table.pieces.insert(piece_index, piece);
+        true
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) -> bool {
+        if !self.ensure_piece_table() || range.end > self.len() || range.start >= range.end {
+            return false;
+        }
+        let start_piece_index = self.split_at(range.start);
+        let end_piece_index = self.split_at(range.end);
+        self.piece_table.as_mut().unwrap().pieces.drain(start_piece_index..end_piece_index);
+        true
+    }
+
+    // Whole content, as lossy utf-8
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes(0..self.len())).to_string()
+    }
+
+    // Content is written to a temporary file which then replaces the file at path, so the mapped file, which may be
+    // the same file, stays readable while writing.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+        let temporary_path = path.with_file_name(format!(".{}.saving", file_name.to_string_lossy()));
+        let mut file = io::BufWriter::new(File::create(&temporary_path)?);
+        match self.piece_table.as_ref() {
+            Some(piece_table) => {
+                for piece in piece_table.pieces.iter() {
+                    file.write_all(&self.source_bytes(piece.source)[piece.start..piece.start + piece.len])?;
+                }
+            }
+            None => file.write_all(&self.mmap[..])?,
+        }
+        file.into_inner()?.sync_all()?;
+        std::fs::rename(&temporary_path, path)
+    }
+
+    pub fn len(&self) -> usize {
+        match self.piece_table.as_ref() {
+            Some(piece_table) => piece_table.pieces.iter().map(|piece| piece.len).sum(),
+            None => self.mmap.len(),
+        }
+    }
+
+    fn ensure_piece_table(&mut self) -> bool {
+        if self.piece_table.is_none() && self.is_indexed() {
+            let line_breaks = self.line_starts.read().unwrap().len() - 1;
+            let pieces = if self.mmap.is_empty() { vec![] } else { vec![Piece { source: Source::Original, start: 0, len: self.mmap.len(), line_breaks }] };
+            self.piece_table = Some(PieceTable { pieces, added: String::new() });
+        }
+        self.piece_table.is_some()
+    }
+
+    fn source_bytes(&self, source: Source) -> &[u8] {
+        match source {
+            Source::Original => &self.mmap[..],
+            Source::Added => self.piece_table.as_ref().map_or(&[][..], |piece_table| piece_table.added.as_bytes()),
+        }
+    }
+
+    // Line breaks in range of the mapped file
+    fn original_line_breaks(&self, range: Range<usize>) -> usize {
+        let line_starts = self.line_starts.read().unwrap();
+        // a line break at position p starts a line at p + 1
+        line_starts.partition_point(|start| *start <= range.end) - line_starts.partition_point(|start| *start <= range.start)
+    }
+
+    // Splits piece containing offset, returns index of the piece starting at offset.
+    fn split_at(&mut self, offset: usize) -> usize {
+        let pieces = &self.piece_table.as_ref().unwrap().pieces;
+        let mut piece_offset = 0;
+        let mut piece_index = 0;
+        while piece_index < pieces.len() && piece_offset + pieces[piece_index].len <= offset {
+            piece_offset += pieces[piece_index].len;
+            piece_index += 1;
+        }
+        if piece_index == pieces.len() || piece_offset == offset {
+            return piece_index;
+        }
+        let Piece { source, start, len, line_breaks } = pieces[piece_index].clone();
+        let split = offset - piece_offset;
+        let left_line_breaks = match source {
+            Source::Original => self.original_line_breaks(start..start + split),
+            Source::Added => bytecount::count(&self.source_bytes(Source::Added)[start..start + split], b'\n'),
+        };
+        let pieces = &mut self.piece_table.as_mut().unwrap().pieces;
+        pieces[piece_index] = Piece { source, start, len: split, line_breaks: left_line_breaks };
+        pieces.insert(piece_index + 1, Piece { source, start: start + split, len: len - split, line_breaks: line_breaks - left_line_breaks });
+        piece_index + 1
+    }
+
+    // Document offset of the start of a line
+    fn line_start(&self, line_index: usize) -> Option<usize> {
+        if line_index == 0 {
+            return Some(0);
+        }
+        let piece_table = match self.piece_table.as_ref() {
+            Some(piece_table) => piece_table,
+            None => return self.line_starts.read().unwrap().get(line_index).copied(),
+        };
+        let mut piece_offset = 0;
+        let mut line_breaks_before = 0;
+        for piece in piece_table.pieces.iter() {
+            if line_breaks_before + piece.line_breaks >= line_index {
+                // line starts after the nth line break of the piece
+                let nth = line_index - line_breaks_before;
+                let line_break_position = match piece.source {
+                    Source::Original => {
+                        let line_starts = self.line_starts.read().unwrap();
+                        line_starts[line_starts.partition_point(|start| *start <= piece.start) + nth - 1] - 1
+                    }
+                    Source::Added => piece.start + piece_table.added.as_bytes()[piece.start..piece.start + piece.len].iter()
+                        .enumerate().filter(|(_, byte)| **byte == b'\n').nth(nth - 1).map(|(i, _)| i)?,
+                };
+                return Some(piece_offset + line_break_position - piece.start + 1);
+            }
+            line_breaks_before += piece.line_breaks;
+            piece_offset += piece.len;
+        }
+        None
+    }
+
+    fn bytes(&self, range: Range<usize>) -> Vec<u8> {
+        let piece_table = match self.piece_table.as_ref() {
+            Some(piece_table) => piece_table,
+            None => return self.mmap[range].to_vec(),
+        };
+        let mut bytes = Vec::with_capacity(range.len());
+        let mut piece_offset = 0;
+        for piece in piece_table.pieces.iter() {
+            let piece_end = piece_offset + piece.len;
+            if piece_end > range.start && piece_offset < range.end {
+                let start = range.start.max(piece_offset) - piece_offset + piece.start;
+                let end = range.end.min(piece_end) - piece_offset + piece.start;
+                bytes.extend_from_slice(&self.source_bytes(piece.source)[start..end]);
+            }
+            if piece_end >= range.end {
+                break;
+            }
+            piece_offset = piece_end;
+        }
+        bytes
+    }
+}
+
+// Mapped document used as the content of an editor
+pub(crate) struct MappedContent {
+    document: MappedDocument,
+    // Whether each line has been read from the document, lines not read yet are empty placeholders
+    loaded_lines: Vec<bool>,
+}
+
+impl MappedContent {
+    fn new(document: MappedDocument) -> Self {
+        Self { document, loaded_lines: vec![false] }
+    }
+
+    // Lines of range not read yet are read from the document into lines.
+    fn load_lines(&mut self, lines: &mut [String], range: Range<usize>) {
+        self.loaded_lines.resize(lines.len(), false);
+        let range = range.start.min(lines.len())..range.end.min(lines.len());
+        let mut start = range.start;
+        while start < range.end {
+            // consecutive lines not loaded yet are read together
+            start += match self.loaded_lines[start..range.end].iter().position(|is_loaded| !is_loaded) {
+                Some(offset) => offset,
+                None => break,
+            };
+            let end = self.loaded_lines[start..range.end].iter().position(|is_loaded| *is_loaded).map_or(range.end, |offset| start + offset);
+            let document_lines = self.document.lines(start..end);
+            for (line_index, line) in (start..).zip(document_lines) {
+                lines[line_index] = line;
+                self.loaded_lines[line_index] = true;
+            }
+            start = end;
+        }
+    }
+
+    fn are_lines_loaded(&self, range: Range<usize>) -> bool {
+        range.into_iter().all(|line_index| self.loaded_lines.get(line_index).copied().unwrap_or(false))
+    }
+
+    // Text between start and end is replaced by text: same edit is applied to the document.
+    fn replace_text(&mut self, start: &Pos<usize>, end: &Pos<usize>, text: &str) {
+        if let (Some(start_offset), Some(end_offset)) = (self.document.offset(start), self.document.offset(end)) {
+            if start_offset < end_offset {
+                self.document.delete(start_offset..end_offset);
+            }
+            self.document.insert(start_offset, text);
+        }
+        // first and last lines keep the part of the replaced lines outside of the edit, which may not be loaded yet
+        let new_lines_count = bytecount::count(text.as_bytes(), b'\n') + 1;
+        let is_start_loaded = self.loaded_lines.get(start.y).copied().unwrap_or(false);
+        let is_end_loaded = self.loaded_lines.get(end.y).copied().unwrap_or(false);
+        let mut new_loaded_lines = vec![true; new_lines_count];
+        new_loaded_lines[0] = is_start_loaded;
+        new_loaded_lines[new_lines_count - 1] &= is_end_loaded;
+        let end_y = (end.y + 1).min(self.loaded_lines.len());
+        self.loaded_lines.splice(start.y.min(end_y)..end_y, new_loaded_lines);
+    }
+}
+
+// Editor content read from a memory mapped document (e.g: huge files) instead of being split into lines up front.
+// Lines around the viewport, the cursor and the selection are read from the document, other lines are empty placeholders
+// until they are displayed. Edits are applied to the document, which is the content saved.
+pub trait MappedLines {
+    // Content is replaced by the document content and history is cleared. Content is read only until document is indexed.
+    fn open_mapped(&mut self, document: MappedDocument);
+    fn mapped_document(&self) -> Option<&MappedDocument>;
+    // Indexing progress of the mapped document, None when content is not mapped or is indexed
+    fn mapped_load_progress(&self) -> Option<LoadProgress>;
+}
+
+impl MappedLines for TextEditor {
+    fn open_mapped(&mut self, document: MappedDocument) {
+        self.lines = vec![String::new()];
+        self.mapped_content = Some(MappedContent::new(document));
+        self.reset_replaced_lines_state();
+        self.update_mapped_lines();
+    }
+
+    fn mapped_document(&self) -> Option<&MappedDocument> {
+        self.mapped_content.as_ref().map(|mapped_content| &mapped_content.document)
+    }
+
+    fn mapped_load_progress(&self) -> Option<LoadProgress> {
+        self.mapped_document().filter(|document| !document.is_indexed()).map(|document| document.progress())
+    }
+}
+
+impl TextEditor {
+    pub(crate) fn is_mapped_content_indexing(&self) -> bool {
+        self.mapped_content.as_ref().map_or(false, |mapped_content| !mapped_content.document.is_indexed())
+    }
+
+    // Lines which can be displayed or edited before the next update are read from the document:
+    // viewport with a margin of one viewport around it, cursor line, selected lines, first and last lines.
+    pub(crate) fn update_mapped_lines(&mut self) {
+        let lines_count = match self.mapped_content.as_ref() {
+            Some(mapped_content) => mapped_content.document.lines_count().max(1),
+            None => return,
+        };
+        // document lines are counted as they are indexed, edits are only possible once it is indexed
+        if lines_count > self.lines.len() {
+            self.lines.resize(lines_count, String::new());
+            self.lines_count = lines_count;
+        }
+        let viewport_lines = (self.text_editor_viewport.height() / self.line_height.max(1.0)) as usize + 1;
+        let first_line_index = self.first_line_index();
+        let mut ranges = vec![
+            first_line_index.saturating_sub(viewport_lines)..first_line_index + 2 * viewport_lines,
+            0..1,
+            self.lines.len() - 1..self.lines.len(),
+            self.cursor_index.y..self.cursor_index.y + 1,
+        ];
+        for (start, end) in [(&self.selection_start_index, &self.selection_end_index), (&self.column_selection_anchor, &self.column_selection_head)] {
+            if let (Some(start), Some(end)) = (start.as_ref(), end.as_ref()) {
+                ranges.push(start.y.min(end.y)..start.y.max(end.y) + 1);
+            }
+        }
+        let mapped_content = self.mapped_content.as_mut().unwrap();
+        for range in ranges {
+            mapped_content.load_lines(&mut self.lines, range);
+        }
+    }
+
+    // Commands computing new lines from a range of lines (e.g: whole text transforms) read them first,
+    // placeholders would replace the content of the document otherwise.
+    pub(crate) fn load_mapped_lines(&mut self, range: Range<usize>) {
+        if let Some(mapped_content) = self.mapped_content.as_mut() {
+            mapped_content.load_lines(&mut self.lines, range);
+        }
+    }
+
+    pub(crate) fn are_mapped_lines_loaded(&self, range: Range<usize>) -> bool {
+        self.mapped_content.as_ref().map_or(true, |mapped_content| mapped_content.are_lines_loaded(range))
+    }
+
+    pub(crate) fn on_mapped_text_replaced(&mut self, start: &Pos<usize>, end: &Pos<usize>, text: &str) {
+        if let Some(mapped_content) = self.mapped_content.as_mut() {
+            mapped_content.replace_text(start, end, text);
+        }
+    }
+
+    // Progress bar at the top of the viewport while document is indexed
+    pub(crate) fn paint_mapped_load_progress(&self, ui: &Ui) {
+        let progress = match self.mapped_load_progress() {
+            Some(progress) => progress,
+            None => return,
+        };
+        ui.ctx().request_repaint_after(IndexingPollInterval);
+        let ratio = progress.indexed_bytes as f32 / progress.total_bytes.max(1) as f32;
+        let viewport = self.text_editor_viewport;
+        let rect = Rect { min: viewport.min, max: Pos2 { x: viewport.min.x + viewport.width() * ratio, y: viewport.min.y + IndexingProgressHeight } };
+        ui.painter().rect_filled(rect, 0.0, self.options.theme.selection_marker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use crate::text_editor::test_support::TemporaryPath;
+    use super::*;
+
+    fn open_indexed(name: &str, text: &str) -> (TemporaryPath, MappedDocument) {
+        let file = TemporaryPath::new(&format!("{}.txt", name));
+        fs::write(file.path(), text).unwrap();
+        let document = MappedDocument::open(file.path()).unwrap();
+        while !document.is_indexed() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        (file, document)
+    }
+
+    fn all_lines(document: &MappedDocument) -> Vec<String> {
+        document.lines(0..document.lines_count())
+    }
+
+    #[test]
+    fn lines_of_mapped_file() {
+        let (_file, document) = open_indexed("mapped-lines", "first\nsecond\n\nlast");
+        assert_eq!(document.lines_count(), 4);
+        assert_eq!(all_lines(&document), vec!["first", "second", "", "last"]);
+        assert_eq!(document.lines(1..3), vec!["second", ""]);
+        assert_eq!(document.lines(3..10), vec!["last"]);
+        assert_eq!(document.line_start(3), Some(14));
+        assert_eq!(document.line_start(4), None);
+        assert_eq!(document.offset(&Pos { x: 2, y: 1 }), Some(8));
+        assert_eq!(document.progress(), LoadProgress { indexed_bytes: 18, total_bytes: 18 });
+    }
+
+    #[test]
+    fn split_at_keeps_line_breaks_of_each_side() {
+        let (_file, mut document) = open_indexed("mapped-split", "ab\ncd\nef");
+        assert!(document.ensure_piece_table());
+        // inside the second line
+        assert_eq!(document.split_at(4), 1);
+        // at an existing piece boundary
+        assert_eq!(document.split_at(4), 1);
+        // at the end
+        assert_eq!(document.split_at(8), 2);
+        let pieces = &document.piece_table.as_ref().unwrap().pieces;
+        assert_eq!(pieces.iter().map(|piece| (piece.start, piece.len, piece.line_breaks)).collect::<Vec<_>>(), vec![(0, 4, 1), (4, 4, 1)]);
+        // right after a line break
+        assert_eq!(document.split_at(3), 1);
+        let pieces = &document.piece_table.as_ref().unwrap().pieces;
+        assert_eq!(pieces.iter().map(|piece| (piece.start, piece.len, piece.line_breaks)).collect::<Vec<_>>(), vec![(0, 3, 1), (3, 1, 0), (4, 4, 1)]);
+        assert_eq!(all_lines(&document), vec!["ab", "cd", "ef"]);
+    }
+
+    #[test]
+    fn lines_across_original_and_added_pieces() {
+        let (_file, mut document) = open_indexed("mapped-edits", "one\ntwo\nthree");
+        // inside "two"
+        assert!(document.insert(6, "NEW\nline\n"));
+        assert_eq!(all_lines(&document), vec!["one", "twNEW", "line", "o", "three"]);
+        assert_eq!(document.lines_count(), 5);
+        assert_eq!(document.line_start(2), Some(10));
+        assert_eq!(document.line_start(3), Some(15));
+        assert_eq!(document.line_start(4), Some(17));
+        // delete across an original, an added and an original piece
+        assert!(document.delete(2..12));
+        assert_eq!(all_lines(&document), vec!["onne", "o", "three"]);
+        // insert into added text, then at the start and at the end
+        assert!(document.insert(3, "\n"));
+        assert!(document.insert(0, "zero\n"));
+        assert!(document.insert(document.len(), "\nfour"));
+        assert_eq!(all_lines(&document), vec!["zero", "onn", "e", "o", "three", "four"]);
+        assert_eq!(document.text(), "zero\nonn\ne\no\nthree\nfour");
+        assert_eq!(document.offset(&Pos { x: 3, y: 4 }), Some(16));
+        // invalid edits
+        assert!(!document.insert(document.len() + 1, "x"));
+        assert!(!document.delete(3..3));
+        assert!(document.delete(0..document.len()));
+        assert_eq!(document.lines_count(), 1);
+        assert_eq!(all_lines(&document), vec![""]);
+    }
+
+    #[test]
+    fn save_edited_document_over_its_mapped_file() {
+        let (file, mut document) = open_indexed("mapped-save", "a\nb\nc");
+        assert!(document.insert(2, "B"));
+        document.save(file.path()).unwrap();
+        // mapped file is still readable after being replaced
+        assert_eq!(all_lines(&document), vec!["a", "Bb", "c"]);
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "a\nBb\nc");
+    }
+
+    #[test]
+    fn trim_mapped_content_after_loading_its_lines() {
+        let (file, document) = open_indexed("mapped-trim", "a  \nb\t\n c \nd ");
+        let mut content = MappedContent::new(document);
+        let mut lines = vec![String::new(); 4];
+        // first and last lines are read on open, lines between are placeholders
+        content.load_lines(&mut lines, 0..1);
+        content.load_lines(&mut lines, 3..4);
+        assert!(!content.are_lines_loaded(0..4));
+        content.load_lines(&mut lines, 0..4);
+        assert!(content.are_lines_loaded(0..4));
+        assert_eq!(lines, vec!["a  ", "b\t", " c ", "d "]);
+        // whole text replacement, as made by the trim lines transform
+        let trimmed_lines: Vec<&str> = lines.iter().map(|line| line.trim_end()).collect();
+        content.replace_text(&Pos { x: 0, y: 0 }, &Pos { x: lines[3].len(), y: 3 }, &trimmed_lines.join("\n"));
+        assert!(content.are_lines_loaded(0..4));
+        content.document.save(file.path()).unwrap();
+        assert_eq!(fs::read_to_string(file.path()).unwrap(), "a\nb\n c\nd");
+    }
+}
@@ -0,0 +1,203 @@
+use std::cell::Ref;
+use eframe::egui::{Color32, Id, Pos2, Rect, Rounding, Sense, Ui};
+use eframe::epaint::{RectShape, Shape};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::text_editor::{word_occurrences_in_line, Pos, Selection, TextEditor};
+use crate::text_editor::unicode::grapheme_width;
+
+pub(crate) const MinimapWidth: f32 = 110.0;
+// Each document line is displayed on 2 pixels, each column on 1 pixel.
+const MinimapLineHeight: f32 = 2.0;
+const MinimapColumnWidth: f32 = 1.0;
+// Markers strip on the right edge of the minimap, scaled to the whole document.
+const OverviewRulerWidth: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub start: Pos<usize>,
+    pub end: Pos<usize>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+}
+
+// Search matches and diagnostics are computed by the application, the minimap displays them as markers.
+pub trait Minimap {
+    fn set_search_matches(&mut self, matches: Vec<(Pos<usize>, Pos<usize>)>);
+    fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>);
+}
+
+impl Minimap for TextEditor {
+    fn set_search_matches(&mut self, matches: Vec<(Pos<usize>, Pos<usize>)>) {
+        self.search_matches = matches;
+    }
+
+    fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics = diagnostics;
+    }
+}
+
+impl TextEditor {
+    // When the document does not fit in the minimap, the minimap scrolls proportionally to the editor.
+    fn minimap_first_line_index(&self, minimap_rect: Rect) -> usize {
+        let rows_count = (minimap_rect.height() / MinimapLineHeight) as usize;
+        if self.lines_count <= rows_count {
+            return 0;
+        }
+        let scrollable_height = (self.lines_count as f32 * self.line_height - self.text_editor_viewport.height()).max(1.0);
+        let scroll_ratio = (self.scroll_offset.y / scrollable_height).min(1.0).max(0.0);
+        (scroll_ratio * (self.lines_count - rows_count) as f32) as usize
+    }
+
+    fn minimap_slider_rect(&self, minimap_rect: Rect) -> Rect {
+        let minimap_first_line_index = self.minimap_first_line_index(minimap_rect) as f32;
+        let visible_lines = self.text_editor_viewport.height() / self.line_height;
+        let top = minimap_rect.min.y + (self.scroll_offset.y / self.line_height - minimap_first_line_index) * MinimapLineHeight;
+        Rect {
+            min: Pos2 { x: minimap_rect.min.x, y: top },
+            max: Pos2 { x: minimap_rect.max.x - OverviewRulerWidth, y: top + visible_lines * MinimapLineHeight },
+        }
+    }
+
+    // Returns the new vertical scroll offset when minimap is clicked or dragged.
+    pub(crate) fn minimap_interaction(&mut self, ui: &Ui, minimap_rect: Rect) -> Option<f32> {
+        let response = ui.interact(minimap_rect, Id::new("text_editor_minimap").with(ui.id()), Sense::click_and_drag());
+        let max_scroll_offset = (self.lines_count as f32 * self.line_height - self.text_editor_viewport.height()).max(0.0);
+        if response.drag_started() || response.clicked() {
+            let pointer = response.interact_pointer_pos()?;
+            if self.minimap_slider_rect(minimap_rect).contains(pointer) && !response.clicked() {
+                return None;
+            }
+            // Jump so clicked line is in the middle of the viewport
+            let line = self.minimap_first_line_index(minimap_rect) as f32 + (pointer.y - minimap_rect.min.y) / MinimapLineHeight;
+            let offset = line * self.line_height - self.text_editor_viewport.height() / 2.0;
+            return Some(offset.max(0.0).min(max_scroll_offset));
+        }
+        if response.dragged() {
+            let delta = response.drag_delta().y;
+            let rows_count = minimap_rect.height() / MinimapLineHeight;
+            let scroll_delta = if self.lines_count as f32 <= rows_count {
+                delta / MinimapLineHeight * self.line_height
+            } else {
+                // slider moves over the whole minimap height for the whole document
+                let slider_height = self.minimap_slider_rect(minimap_rect).height();
+                delta * max_scroll_offset / (minimap_rect.height() - slider_height).max(1.0)
+            };
+            return Some((self.scroll_offset.y + scroll_delta).max(0.0).min(max_scroll_offset));
+        }
+        None
+    }
+
+    pub(crate) fn paint_minimap(&self, ui: &Ui, minimap_rect: Rect) {
+        let theme = &self.options.theme;
+        let mut shapes = vec![rect_shape(minimap_rect, theme.minimap_background)];
+        let minimap_first_line_index = self.minimap_first_line_index(minimap_rect);
+        let rows_count = (minimap_rect.height() / MinimapLineHeight) as usize;
+        let max_columns = ((minimap_rect.width() - OverviewRulerWidth) / MinimapColumnWidth) as usize;
+        let last_line_index = (minimap_first_line_index + rows_count).min(self.lines.len());
+        for (row, line) in self.lines[minimap_first_line_index..last_line_index].iter().enumerate() {
+            let y = minimap_rect.min.y + row as f32 * MinimapLineHeight;
+            // each run of non whitespace characters is drawn as a block
+            let mut column = 0;
+            let mut run_start: Option<usize> = None;
+            for grapheme in line.graphemes(true) {
+                if column >= max_columns {
+                    break;
+                }
+                let is_whitespace = grapheme.chars().all(char::is_whitespace);
+                if is_whitespace && run_start.is_some() {
+                    shapes.push(self.minimap_run_shape(minimap_rect.min.x, y, run_start.take().unwrap(), column));
+                } else if !is_whitespace && run_start.is_none() {
+                    run_start = Some(column);
+                }
+                column += grapheme_width(grapheme);
+            }
+            if let Some(start) = run_start {
+                shapes.push(self.minimap_run_shape(minimap_rect.min.x, y, start, column.min(max_columns)));
+            }
+        }
+        shapes.push(rect_shape(self.minimap_slider_rect(minimap_rect), theme.minimap_slider));
+        self.paint_overview_markers(minimap_rect, &mut shapes);
+        ui.painter().with_clip_rect(minimap_rect).extend(shapes);
+    }
+
+    fn minimap_run_shape(&self, left: f32, y: f32, start_column: usize, end_column: usize) -> Shape {
+        rect_shape(Rect {
+            min: Pos2 { x: left + start_column as f32 * MinimapColumnWidth, y },
+            max: Pos2 { x: left + end_column as f32 * MinimapColumnWidth, y: y + MinimapLineHeight * 0.75 },
+        }, self.options.theme.minimap_text)
+    }
+
+    // Painted occurrences only cover the rendered lines, the overview ruler needs the whole document.
+    // Lines are searched again only when the highlighted word or the text changes.
+    fn overview_word_occurrence_lines(&self) -> Ref<Vec<usize>> {
+        let edit_signature = self.edit_signature();
+        let is_outdated = {
+            let (word, signature, _) = &*self.overview_word_occurrences.borrow();
+            *word != self.highlighted_word || (word.is_some() && *signature != edit_signature)
+        };
+        if is_outdated {
+            let lines = match self.highlighted_word.as_ref() {
+                Some(highlighted_word) => {
+                    let graphemes = highlighted_word.graphemes(true).collect::<Vec<&str>>();
+                    self.lines.iter().enumerate()
+                        .filter(|(_, line)| !word_occurrences_in_line(&graphemes, line).is_empty())
+                        .map(|(line_index, _)| line_index)
+                        .collect()
+                }
+                None => vec![],
+            };
+            *self.overview_word_occurrences.borrow_mut() = (self.highlighted_word.clone(), edit_signature, lines);
+        }
+        Ref::map(self.overview_word_occurrences.borrow(), |(_, _, lines)| lines)
+    }
+
+    fn paint_overview_markers(&self, minimap_rect: Rect, shapes: &mut Vec<Shape>) {
+        let theme = &self.options.theme;
+        let ruler_left = minimap_rect.max.x - OverviewRulerWidth;
+        let lines_count = self.lines_count.max(1) as f32;
+        let mut marker = |start_y: usize, end_y: usize, color: Color32, left: f32, right: f32| {
+            let top = minimap_rect.min.y + start_y as f32 / lines_count * minimap_rect.height();
+            let bottom = minimap_rect.min.y + (end_y + 1) as f32 / lines_count * minimap_rect.height();
+            shapes.push(rect_shape(Rect {
+                min: Pos2 { x: left, y: top },
+                max: Pos2 { x: right, y: bottom.max(top + 2.0) },
+            }, color));
+        };
+        let middle = ruler_left + OverviewRulerWidth / 2.0;
+        if self.has_selection() {
+            marker(self.selection_start_index.as_ref().unwrap().y, self.selection_end_index.as_ref().unwrap().y, theme.selection_marker, ruler_left, middle);
+        }
+        for &line_index in self.overview_word_occurrence_lines().iter() {
+            marker(line_index, line_index, theme.word_occurrence_marker, ruler_left, middle);
+        }
+        for (start, end) in self.search_matches.iter() {
+            marker(start.y, end.y, theme.search_match_marker, ruler_left, middle);
+        }
+        for diagnostic in self.diagnostics.iter() {
+            let color = match diagnostic.severity {
+                DiagnosticSeverity::Error => theme.error_marker,
+                DiagnosticSeverity::Warning => theme.warning_marker,
+                DiagnosticSeverity::Info => theme.info_marker,
+            };
+            marker(diagnostic.start.y, diagnostic.end.y, color, middle, minimap_rect.max.x);
+        }
+    }
+}
+
+fn rect_shape(rect: Rect, fill: Color32) -> Shape {
+    Shape::Rect(RectShape {
+        rect,
+        rounding: Rounding::ZERO,
+        fill,
+        stroke: Default::default(),
+        fill_texture_id: Default::default(),
+        uv: Rect::ZERO,
+    })
+}
@@ -0,0 +1,327 @@
+use std::ops::Range;
+use crate::text_editor::{Pos, TextEditor};
+use crate::text_editor::tail::TailMode;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug, Clone)]
+pub(crate) struct ProtectedRange {
+    start: Pos<usize>,
+    // Exclusive. For lines, start of the line following the last protected line
+    end: Pos<usize>,
+    is_lines: bool,
+}
+
+// Parts of the content which can't be edited (e.g: generated code). Edits touching them are rejected, edits around them
+// move them along with their text. Content replaced by the application (e.g: reload from disk) is not checked.
+pub trait ProtectedRanges {
+    // Lines can't be edited, removed or joined with their neighbours. New lines can be inserted before and after them.
+    fn protect_lines(&mut self, lines: Range<usize>);
+    // Graphemes between start (inclusive) and end (exclusive) can't be edited. Text can be inserted before and after them.
+    fn protect_range(&mut self, start: Pos<usize>, end: Pos<usize>);
+    fn clear_protected_ranges(&mut self);
+    fn is_protected(&self, pos: &Pos<usize>) -> bool;
+}
+
+impl ProtectedRanges for TextEditor {
+    fn protect_lines(&mut self, lines: Range<usize>) {
+        if lines.is_empty() {
+            return;
+        }
+        self.protected_ranges.push(ProtectedRange { start: Pos { x: 0, y: lines.start }, end: Pos { x: 0, y: lines.end }, is_lines: true });
+    }
+
+    fn protect_range(&mut self, start: Pos<usize>, end: Pos<usize>) {
+        if !Self::is_before(&start, &end) {
+            return;
+        }
+        self.protected_ranges.push(ProtectedRange { start, end, is_lines: false });
+    }
+
+    fn clear_protected_ranges(&mut self) {
+        self.protected_ranges.clear();
+    }
+
+    fn is_protected(&self, pos: &Pos<usize>) -> bool {
+        self.protected_ranges.iter().any(|range| if range.is_lines {
+            range.start.y <= pos.y && pos.y < range.end.y
+        } else {
+            !Self::is_before(pos, &range.start) && Self::is_before(pos, &range.end)
+        })
+    }
+}
+
+impl TextEditor {
+    // Content of a followed file only changes with the file, a mapped document can only be edited once indexed.
+    pub(crate) fn is_read_only(&self) -> bool {
+        self.options.read_only || self.is_following() || self.is_mapped_content_indexing()
+    }
+
+    // Whether text between start and end can be replaced: editor is not read only and no protected range is touched.
+    // An empty range is an insertion at start.
+    pub(crate) fn is_edit_allowed(&self, start: &Pos<usize>, end: &Pos<usize>) -> bool {
+        !self.is_read_only() && !touches_protected_range(&self.protected_ranges, start, end)
+    }
+
+    // Whether lines from start_index to end_index (inclusive) can be replaced by new_lines. Only changed lines are checked,
+    // unchanged first and last lines (e.g: kept around inserted lines) can be protected.
+    // Lines of a mapped document not read yet are placeholders, new lines computed from them would erase its content.
+    pub(crate) fn is_lines_replacement_allowed(&self, start_index: usize, end_index: usize, new_lines: &[String]) -> bool {
+        !self.is_read_only() && self.are_mapped_lines_loaded(start_index..end_index.min(self.lines.len() - 1) + 1)
+            && !touches_protected_lines(&self.protected_ranges, &self.lines, start_index, end_index, new_lines)
+    }
+
+    pub(crate) fn shift_protected_ranges(&mut self, end: &Pos<usize>, new_end: &Pos<usize>) {
+        shift_protected_ranges(&mut self.protected_ranges, end, new_end);
+    }
+
+    // Lines from start_index to end_index (inclusive) are replaced by new_lines, called before lines are changed.
+    pub(crate) fn shift_protected_lines(&mut self, start_index: usize, end_index: usize, new_lines: &[String]) {
+        shift_protected_lines(&mut self.protected_ranges, &self.lines, start_index, end_index, new_lines);
+    }
+
+    // Start, end and new end of the text actually changed when lines are replaced, called before lines are changed.
+    pub(crate) fn lines_replacement_bounds(&self, start_index: usize, end_index: usize, new_lines: &[String]) -> (Pos<usize>, Pos<usize>, Pos<usize>) {
+        match lines_change(&self.lines, start_index, end_index, new_lines) {
+            LinesChange::Lines { start_index, end_index, new_lines_count } =>
+                (Pos { x: 0, y: start_index }, Pos { x: 0, y: end_index }, Pos { x: 0, y: start_index + new_lines_count }),
+            LinesChange::Text { start, end, new_end } => (start, end, new_end),
+        }
+    }
+}
+
+// Part of the content actually changed when lines are replaced
+enum LinesChange {
+    // Whole lines from start_index (inclusive) to end_index (exclusive) are replaced by new_lines_count whole lines
+    Lines { start_index: usize, end_index: usize, new_lines_count: usize },
+    // Text between start and end is replaced by text ending at new_end
+    Text { start: Pos<usize>, end: Pos<usize>, new_end: Pos<usize> },
+}
+
+// Lines from start_index to end_index (inclusive) are replaced by new_lines. Unchanged first and last lines, which history
+// keeps around inserted or removed lines, are not part of the change.
+fn lines_change(lines: &[String], start_index: usize, end_index: usize, new_lines: &[String]) -> LinesChange {
+    let end_index = end_index.min(lines.len() - 1);
+    let replaced_lines = &lines[start_index..=end_index];
+    let prefix = replaced_lines.iter().zip(new_lines.iter()).take_while(|(line, new_line)| line == new_line).count();
+    let max_suffix = replaced_lines.len().min(new_lines.len()) - prefix;
+    let suffix = replaced_lines.iter().rev().zip(new_lines.iter().rev()).take(max_suffix).take_while(|(line, new_line)| line == new_line).count();
+    let replaced = start_index + prefix..end_index + 1 - suffix;
+    let inserted = prefix..new_lines.len() - suffix;
+    if replaced.is_empty() || inserted.is_empty() {
+        return LinesChange::Lines { start_index: replaced.start, end_index: replaced.end, new_lines_count: inserted.len() };
+    }
+    let text = lines[replaced.clone()].join("\n");
+    let new_text = new_lines[inserted].join("\n");
+    let graphemes = text.graphemes(true).collect::<Vec<&str>>();
+    let new_graphemes = new_text.graphemes(true).collect::<Vec<&str>>();
+    let prefix = graphemes.iter().zip(new_graphemes.iter()).take_while(|(grapheme, new_grapheme)| grapheme == new_grapheme).count();
+    let max_suffix = graphemes.len().min(new_graphemes.len()) - prefix;
+    let suffix = graphemes.iter().rev().zip(new_graphemes.iter().rev()).take(max_suffix).take_while(|(grapheme, new_grapheme)| grapheme == new_grapheme).count();
+    LinesChange::Text {
+        start: graphemes_end(&graphemes[..prefix], replaced.start),
+        end: graphemes_end(&graphemes[..graphemes.len() - suffix], replaced.start),
+        new_end: graphemes_end(&new_graphemes[..new_graphemes.len() - suffix], replaced.start),
+    }
+}
+
+// Position after graphemes of lines starting at line start_index
+fn graphemes_end(graphemes: &[&str], start_index: usize) -> Pos<usize> {
+    graphemes.iter().fold(Pos { x: 0, y: start_index }, |pos, grapheme| if grapheme.ends_with('\n') {
+        Pos { x: 0, y: pos.y + 1 }
+    } else {
+        Pos { x: pos.x + 1, y: pos.y }
+    })
+}
+
+fn touches_protected_lines(protected_ranges: &[ProtectedRange], lines: &[String], start_index: usize, end_index: usize, new_lines: &[String]) -> bool {
+    match lines_change(lines, start_index, end_index, new_lines) {
+        LinesChange::Lines { start_index, end_index, new_lines_count } => {
+            if start_index == end_index && new_lines_count == 0 {
+                return false;
+            }
+            // protected lines are neither removed nor split, lines can be inserted before and after them
+            let (start, end) = (Pos { x: 0, y: start_index }, Pos { x: 0, y: end_index });
+            protected_ranges.iter().any(|range| if range.is_lines && start_index == end_index {
+                range.start.y < start_index && start_index < range.end.y
+            } else if range.is_lines {
+                start_index < range.end.y && range.start.y < end_index
+            } else {
+                touches(range, &start, &end)
+            })
+        }
+        LinesChange::Text { start, end, .. } => touches_protected_range(protected_ranges, &start, &end),
+    }
+}
+
+fn touches_protected_range(protected_ranges: &[ProtectedRange], start: &Pos<usize>, end: &Pos<usize>) -> bool {
+    protected_ranges.iter().any(|range| touches(range, start, end))
+}
+
+fn touches(range: &ProtectedRange, start: &Pos<usize>, end: &Pos<usize>) -> bool {
+    if range.is_lines {
+        start.y < range.end.y && end.y >= range.start.y
+    } else if start.y == end.y && start.x == end.x {
+        TextEditor::is_before(&range.start, start) && TextEditor::is_before(start, &range.end)
+    } else {
+        TextEditor::is_before(start, &range.end) && TextEditor::is_before(&range.start, end)
+    }
+}
+
+fn shift_protected_lines(protected_ranges: &mut [ProtectedRange], lines: &[String], start_index: usize, end_index: usize, new_lines: &[String]) {
+    match lines_change(lines, start_index, end_index, new_lines) {
+        LinesChange::Lines { start_index, end_index, new_lines_count } =>
+            shift_protected_ranges(protected_ranges, &Pos { x: 0, y: end_index }, &Pos { x: 0, y: start_index + new_lines_count }),
+        LinesChange::Text { end, new_end, .. } => shift_protected_ranges(protected_ranges, &end, &new_end),
+    }
+}
+
+// Text ending at end has been replaced by text ending at new_end, ranges after it follow their text.
+// Text inserted right after a range is not part of it.
+fn shift_protected_ranges(protected_ranges: &mut [ProtectedRange], end: &Pos<usize>, new_end: &Pos<usize>) {
+    let shift = |pos: &mut Pos<usize>| {
+        if pos.y == end.y {
+            pos.x = new_end.x + pos.x - end.x;
+        }
+        pos.y = pos.y + new_end.y - end.y;
+    };
+    for range in protected_ranges.iter_mut() {
+        if !TextEditor::is_before(&range.start, end) {
+            shift(&mut range.start);
+        }
+        if TextEditor::is_before(end, &range.end) {
+            shift(&mut range.end);
+        }
+        if range.is_lines {
+            range.start.x = 0;
+            range.end.x = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text_editor::test_support::lines;
+    use super::*;
+
+    fn protected_lines(lines: Range<usize>) -> ProtectedRange {
+        ProtectedRange { start: Pos { x: 0, y: lines.start }, end: Pos { x: 0, y: lines.end }, is_lines: true }
+    }
+
+    fn protected_range(start: (usize, usize), end: (usize, usize)) -> ProtectedRange {
+        ProtectedRange { start: Pos { x: start.0, y: start.1 }, end: Pos { x: end.0, y: end.1 }, is_lines: false }
+    }
+
+    fn bounds(protected_ranges: &[ProtectedRange]) -> Vec<((usize, usize), (usize, usize))> {
+        protected_ranges.iter().map(|range| ((range.start.x, range.start.y), (range.end.x, range.end.y))).collect()
+    }
+
+    fn touches(protected_ranges: &[ProtectedRange], start: (usize, usize), end: (usize, usize)) -> bool {
+        touches_protected_range(protected_ranges, &Pos { x: start.0, y: start.1 }, &Pos { x: end.0, y: end.1 })
+    }
+
+    fn shift(protected_ranges: &mut [ProtectedRange], end: (usize, usize), new_end: (usize, usize)) {
+        shift_protected_ranges(protected_ranges, &Pos { x: end.0, y: end.1 }, &Pos { x: new_end.0, y: new_end.1 });
+    }
+
+    #[test]
+    fn insert_before_and_after_protected_lines() {
+        // lines 2 and 3 are protected
+        let mut protected_ranges = vec![protected_lines(2..4)];
+        // typing before, inside and after the block
+        assert!(!touches(&protected_ranges, (5, 1), (5, 1)));
+        assert!(touches(&protected_ranges, (0, 2), (0, 2)));
+        assert!(touches(&protected_ranges, (3, 3), (3, 3)));
+        assert!(!touches(&protected_ranges, (0, 4), (0, 4)));
+        // new line at the end of line 1 moves the block down
+        shift(&mut protected_ranges, (5, 1), (0, 2));
+        assert_eq!(bounds(&protected_ranges), vec![((0, 3), (0, 5))]);
+        // text inserted after the block does not move it
+        shift(&mut protected_ranges, (0, 5), (3, 6));
+        assert_eq!(bounds(&protected_ranges), vec![((0, 3), (0, 5))]);
+    }
+
+    #[test]
+    fn insert_before_and_after_protected_range() {
+        // "cd" of "abcdef" on line 1 is protected
+        let mut protected_ranges = vec![protected_range((2, 1), (4, 1))];
+        assert!(!touches(&protected_ranges, (2, 1), (2, 1)));
+        assert!(touches(&protected_ranges, (3, 1), (3, 1)));
+        assert!(!touches(&protected_ranges, (4, 1), (4, 1)));
+        assert!(touches(&protected_ranges, (1, 1), (3, 1)));
+        assert!(!touches(&protected_ranges, (4, 1), (6, 1)));
+        // insertion right before the range moves it, insertion right after it does not
+        shift(&mut protected_ranges, (2, 1), (5, 1));
+        assert_eq!(bounds(&protected_ranges), vec![((5, 1), (7, 1))]);
+        shift(&mut protected_ranges, (7, 1), (9, 1));
+        assert_eq!(bounds(&protected_ranges), vec![((5, 1), (7, 1))]);
+        // line break before the range moves it to the start of a new line
+        shift(&mut protected_ranges, (1, 1), (0, 2));
+        assert_eq!(bounds(&protected_ranges), vec![((4, 2), (6, 2))]);
+        // lines removed before it move it up
+        shift(&mut protected_ranges, (0, 2), (0, 0));
+        assert_eq!(bounds(&protected_ranges), vec![((4, 0), (6, 0))]);
+    }
+
+    #[test]
+    fn join_lines_across_protected_block() {
+        let protected_ranges = vec![protected_lines(2..4)];
+        // joining the line before the block with its first line, or its last line with the line after it
+        assert!(touches(&protected_ranges, (5, 1), (0, 2)));
+        assert!(touches(&protected_ranges, (5, 3), (0, 4)));
+        assert!(touches(&protected_ranges, (5, 0), (0, 5)));
+        // joining lines after the block
+        assert!(!touches(&protected_ranges, (5, 4), (0, 5)));
+        // a protected range at the start of a line is not changed by joining it with the previous line, it follows its text
+        let mut protected_ranges = vec![protected_range((0, 2), (3, 2))];
+        assert!(!touches(&protected_ranges, (5, 1), (0, 2)));
+        shift(&mut protected_ranges, (0, 2), (5, 1));
+        assert_eq!(bounds(&protected_ranges), vec![((5, 1), (8, 1))]);
+    }
+
+    #[test]
+    fn replace_lines_around_protected_block() {
+        let text = lines("zero\none\ntwo\nthree\nfour");
+        let mut protected_ranges = vec![protected_lines(2..4)];
+        let replacement = |new_lines: &str| lines(new_lines);
+        // lines inserted after line 1, which is kept along with the first protected line
+        assert!(!touches_protected_lines(&protected_ranges, &text, 1, 2, &replacement("one\nnew\ntwo")));
+        // lines inserted inside the block
+        assert!(touches_protected_lines(&protected_ranges, &text, 2, 3, &replacement("two\nnew\nthree")));
+        // protected line edited
+        assert!(touches_protected_lines(&protected_ranges, &text, 1, 2, &replacement("one\nTWO")));
+        // lines before the block removed, as a replacement which keeps its first line
+        assert!(!touches_protected_lines(&protected_ranges, &text, 0, 2, &replacement("two")));
+        shift_protected_lines(&mut protected_ranges, &text, 0, 2, &replacement("two"));
+        assert_eq!(bounds(&protected_ranges), vec![((0, 0), (0, 2))]);
+        // undo of the removal: lines are inserted back before the block, which keeps its lines
+        let text = lines("two\nthree\nfour");
+        assert!(!touches_protected_lines(&protected_ranges, &text, 0, 0, &replacement("zero\none\ntwo")));
+        shift_protected_lines(&mut protected_ranges, &text, 0, 0, &replacement("zero\none\ntwo"));
+        assert_eq!(bounds(&protected_ranges), vec![((0, 2), (0, 4))]);
+    }
+
+    #[test]
+    fn undo_of_line_break_before_protected_range() {
+        // "cd" of "abcdef" is protected
+        let mut protected_ranges = vec![protected_range((2, 0), (4, 0))];
+        // "ab|cdef" -> "ab\n|cdef"
+        assert!(!touches(&protected_ranges, (2, 0), (2, 0)));
+        shift(&mut protected_ranges, (2, 0), (0, 1));
+        assert_eq!(bounds(&protected_ranges), vec![((0, 1), (2, 1))]);
+        // undo replaces lines 0 and 1 by the line before the edit, only the line break is removed
+        let text = lines("ab\ncdef");
+        assert!(!touches_protected_lines(&protected_ranges, &text, 0, 1, &lines("abcdef")));
+        shift_protected_lines(&mut protected_ranges, &text, 0, 1, &lines("abcdef"));
+        assert_eq!(bounds(&protected_ranges), vec![((2, 0), (4, 0))]);
+        // undo of an edit of protected text is rejected
+        assert!(touches_protected_lines(&protected_ranges, &lines("abcdef"), 0, 0, &lines("abCdef")));
+    }
+
+    #[test]
+    fn remove_last_line_after_protected_line() {
+        // history keeps the previous line when the last line is removed
+        let protected_ranges = vec![protected_lines(0..1)];
+        assert!(!touches_protected_lines(&protected_ranges, &lines("b\nab"), 0, 1, &lines("b")));
+        assert!(touches_protected_lines(&protected_ranges, &lines("b\nab"), 0, 1, &lines("ab")));
+    }
+}
@@ -0,0 +1,66 @@
+use eframe::egui::{Pos2, Rect, Rounding, Stroke};
+use eframe::epaint::{RectShape, Shape};
+use crate::text_editor::TextEditor;
+
+// Background band behind the cursor line, vertical rulers at configured columns, and line or range backgrounds set by
+// widgets built on the editor.
+pub(crate) trait LineDecorations {
+    fn paint_line_backgrounds(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>);
+    fn paint_current_line(&self, first_line_index: usize, shapes: &mut Vec<Shape>);
+    fn paint_rulers(&self, shapes: &mut Vec<Shape>);
+}
+
+impl LineDecorations for TextEditor {
+    fn paint_line_backgrounds(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>) {
+        let background = |min: Pos2, max: Pos2, fill| Shape::Rect(RectShape {
+            rect: Rect { min, max },
+            rounding: Rounding::ZERO,
+            fill,
+            stroke: Default::default(),
+            fill_texture_id: Default::default(),
+            uv: Rect::ZERO,
+        });
+        let start = self.line_backgrounds.partition_point(|(line_index, _)| *line_index < first_line_index);
+        for (line_index, color) in self.line_backgrounds[start..].iter().take_while(|(line_index, _)| *line_index < last_line_index) {
+            let top = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(*line_index, first_line_index);
+            shapes.push(background(Pos2 { x: self.text_editor_viewport.min.x, y: top },
+                                   Pos2 { x: self.text_editor_viewport.max.x, y: top + self.line_height }, *color));
+        }
+        let start = self.range_backgrounds.partition_point(|(start, _, _)| start.y < first_line_index);
+        for (start, end, color) in self.range_backgrounds[start..].iter().take_while(|(start, _, _)| start.y < last_line_index) {
+            let top = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(start.y, first_line_index);
+            shapes.push(background(Pos2 { x: self.index_to_x(start), y: top },
+                                   Pos2 { x: self.index_to_x(end), y: top + self.line_height }, *color));
+        }
+    }
+
+    fn paint_current_line(&self, first_line_index: usize, shapes: &mut Vec<Shape>) {
+        if self.cursor_index.y < first_line_index {
+            return;
+        }
+        let top = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(self.cursor_index.y, first_line_index);
+        shapes.push(Shape::Rect(RectShape {
+            rect: Rect {
+                min: Pos2 { x: self.text_editor_viewport.min.x, y: top },
+                max: Pos2 { x: self.text_editor_viewport.max.x, y: top + self.line_height },
+            },
+            rounding: Rounding::ZERO,
+            fill: self.options.theme.current_line_background,
+            stroke: Default::default(),
+            fill_texture_id: Default::default(),
+            uv: Rect::ZERO,
+        }));
+    }
+
+    fn paint_rulers(&self, shapes: &mut Vec<Shape>) {
+        for column in self.options.rulers.iter() {
+            // rulers follow horizontal scroll, those scrolled out of the viewport are not painted.
+            let x = self.column_to_x(*column);
+            if x < self.text_editor_viewport.min.x || x > self.text_editor_viewport.max.x {
+                continue;
+            }
+            shapes.push(Shape::line_segment([Pos2 { x, y: self.text_editor_viewport.min.y }, Pos2 { x, y: self.text_editor_viewport.max.y }],
+                                            Stroke::new(1.0, self.options.theme.ruler)));
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use eframe::egui;
+use eframe::egui::{Color32, Pos2, Rect, Stroke, Ui};
+use crate::text_editor::{Pos, State, TextEditor, UnsavedState};
+use crate::text_editor::Selection;
+use crate::text_editor::blame::Blame;
+use crate::text_editor::git_gutter::GitChanges;
+use crate::text_editor::mapped_document::MappedContent;
+use crate::text_editor::read_only::ProtectedRange;
+use crate::text_editor::tail::Tail;
+use crate::text_editor::events::{EditorEvent, EventQueue};
+
+const SplitSeparatorWidth: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SplitDirection {
+    // Views are one above the other
+    Horizontal,
+    // Views are side by side
+    Vertical,
+}
+
+// Content of a document, shared by all views on it. Everything else in TextEditor is view state:
+// viewport, scroll offset, cursor, selection...
+#[derive(Default)]
+pub(crate) struct Buffer {
+    lines: Vec<String>,
+    lines_count: usize,
+    history: Vec<State>,
+    history_index: usize,
+    unsaved_stated: Option<UnsavedState>,
+    saved_history_len: Option<usize>,
+    mapped_content: Option<MappedContent>,
+    protected_ranges: Vec<ProtectedRange>,
+    view_edits: Option<Vec<ReplacedRange>>,
+    event_queue: EventQueue,
+    // Git markers and blame refer to lines, a followed file appends to them
+    git_changes: GitChanges,
+    blame: Blame,
+    tail: Option<Tail>,
+}
+
+// Text between start and end has been replaced by text ending at new_end.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReplacedRange {
+    start: Pos<usize>,
+    end: Pos<usize>,
+    new_end: Pos<usize>,
+}
+
+impl Buffer {
+    pub(crate) fn new(lines: Vec<String>) -> Self {
+        Self { lines_count: lines.len(), lines, saved_history_len: Some(0), ..Default::default() }
+    }
+}
+
+// Two views on the same document. The buffer lives in the active view, it is lent to the other view while it is rendered,
+// so edits in one view are immediately visible in the other one.
+pub struct SplitView {
+    views: [TextEditor; 2],
+    active_view_index: usize,
+    direction: SplitDirection,
+    // Part of the space given to the first view
+    ratio: f32,
+}
+
+impl SplitView {
+    pub fn new(mut text_editor: TextEditor, direction: SplitDirection) -> Self {
+        // edits of one view are recorded, so the other view can shift its cursor and selection when it gets the buffer
+        text_editor.view_edits = Some(vec![]);
+        let second_view = text_editor.new_view();
+        Self {
+            views: [text_editor, second_view],
+            active_view_index: 0,
+            direction,
+            ratio: 0.5,
+        }
+    }
+
+    // Back to a single view, the active one.
+    pub fn into_text_editor(self) -> TextEditor {
+        let [first_view, second_view] = self.views;
+        let mut text_editor = if self.active_view_index == 0 { first_view } else { second_view };
+        text_editor.view_edits = None;
+        text_editor.has_keyboard_focus = true;
+        text_editor
+    }
+
+    pub fn direction(&self) -> SplitDirection {
+        self.direction
+    }
+
+    pub fn set_direction(&mut self, direction: SplitDirection) {
+        self.direction = direction;
+    }
+
+    pub fn active_view_mut(&mut self) -> &mut TextEditor {
+        &mut self.views[self.active_view_index]
+    }
+
+    // Events of both views since last call, they are queued in the buffer which the active view owns between frames.
+    pub fn take_events(&mut self) -> Vec<EditorEvent> {
+        self.active_view_mut().take_events()
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, ui: &mut Ui) {
+        let (first_rect, separator_rect, second_rect) = self.layout(ui.max_rect());
+        let rects = [first_rect, second_rect];
+        // Pressing in a view makes it the active one: it receives keyboard input and owns the buffer.
+        let maybe_pressed_pos = ui.input(|input| if input.pointer.any_pressed() { input.pointer.interact_pos() } else { None });
+        if let Some(pressed_view_index) = maybe_pressed_pos.and_then(|pos| rects.iter().position(|rect| rect.contains(pos))) {
+            self.activate_view(pressed_view_index);
+        }
+        for (view_index, rect) in rects.iter().enumerate() {
+            let is_active = view_index == self.active_view_index;
+            if !is_active {
+                let buffer = self.views[self.active_view_index].take_buffer();
+                self.views[view_index].put_buffer(buffer);
+            }
+            let view = &mut self.views[view_index];
+            view.has_keyboard_focus = is_active;
+            ui.allocate_ui_at_rect(*rect, |ui| ui.push_id(view_index, |ui| view.ui(ctx, ui)));
+            if !is_active {
+                let buffer = self.views[view_index].take_buffer();
+                self.views[self.active_view_index].put_buffer(buffer);
+            }
+        }
+        ui.painter().rect_filled(separator_rect, 0.0, Color32::LIGHT_GRAY);
+        let separator = ui.interact(separator_rect, ui.id().with("split_view_separator"), egui::Sense::drag());
+        if separator.dragged() {
+            let max_rect = ui.max_rect();
+            let delta = separator.drag_delta();
+            self.ratio = match self.direction {
+                SplitDirection::Horizontal => self.ratio + delta.y / max_rect.height(),
+                SplitDirection::Vertical => self.ratio + delta.x / max_rect.width(),
+            }.max(0.1).min(0.9);
+        }
+        if separator.hovered() || separator.dragged() {
+            ui.output_mut(|output| output.cursor_icon = match self.direction {
+                SplitDirection::Horizontal => egui::CursorIcon::ResizeVertical,
+                SplitDirection::Vertical => egui::CursorIcon::ResizeHorizontal,
+            });
+            ui.painter().rect_stroke(separator_rect, 0.0, Stroke::new(1.0, Color32::GRAY));
+        }
+    }
+
+    fn activate_view(&mut self, view_index: usize) {
+        if view_index == self.active_view_index {
+            return;
+        }
+        let buffer = self.views[self.active_view_index].take_buffer();
+        self.views[view_index].put_buffer(buffer);
+        self.active_view_index = view_index;
+    }
+
+    fn layout(&self, rect: Rect) -> (Rect, Rect, Rect) {
+        match self.direction {
+            SplitDirection::Horizontal => {
+                let y = rect.min.y + (rect.height() - SplitSeparatorWidth) * self.ratio;
+                (Rect { min: rect.min, max: Pos2 { x: rect.max.x, y } },
+                 Rect { min: Pos2 { x: rect.min.x, y }, max: Pos2 { x: rect.max.x, y: y + SplitSeparatorWidth } },
+                 Rect { min: Pos2 { x: rect.min.x, y: y + SplitSeparatorWidth }, max: rect.max })
+            }
+            SplitDirection::Vertical => {
+                let x = rect.min.x + (rect.width() - SplitSeparatorWidth) * self.ratio;
+                (Rect { min: rect.min, max: Pos2 { x, y: rect.max.y } },
+                 Rect { min: Pos2 { x, y: rect.min.y }, max: Pos2 { x: x + SplitSeparatorWidth, y: rect.max.y } },
+                 Rect { min: Pos2 { x: x + SplitSeparatorWidth, y: rect.min.y }, max: rect.max })
+            }
+        }
+    }
+}
+
+impl TextEditor {
+    // A view on the same document, with its own glyph brushes as both views are rendered in the same frame.
+    pub(crate) fn new_view(&self) -> TextEditor {
+        let glyph_brushes = Self::build_glyph_brushes(self.gl.as_ref(), &self.fonts);
+        let mut view = Self::with_shared_resources(self.gl.clone(), self.fonts.clone(), glyph_brushes, String::new(), self.options.clone());
+        if view.zoom != self.zoom {
+            view.zoom = self.zoom;
+            view.update_font_metrics();
+        }
+        view.take_buffer();
+        view.scroll_offset = self.scroll_offset.clone();
+        view.cursor_index = self.cursor_index.clone();
+        view.cursor_pos = self.cursor_pos.clone();
+        view.scroll_to_offset_y = Some(self.scroll_offset.y);
+        view
+    }
+
+    pub(crate) fn take_buffer(&mut self) -> Buffer {
+        Buffer {
+            lines: std::mem::take(&mut self.lines),
+            lines_count: std::mem::take(&mut self.lines_count),
+            history: std::mem::take(&mut self.history),
+            history_index: std::mem::take(&mut self.history_index),
+            unsaved_stated: self.unsaved_stated.take(),
+            saved_history_len: self.saved_history_len.take(),
+            mapped_content: self.mapped_content.take(),
+            protected_ranges: std::mem::take(&mut self.protected_ranges),
+            view_edits: self.view_edits.take(),
+            event_queue: std::mem::take(&mut self.event_queue),
+            git_changes: std::mem::take(&mut self.git_changes),
+            blame: std::mem::take(&mut self.blame),
+            tail: self.tail.take(),
+        }
+    }
+
+    // Buffer may have been edited through another view: cursor and selection are kept within its bounds.
+    pub(crate) fn put_buffer(&mut self, buffer: Buffer) {
+        self.lines = buffer.lines;
+        self.lines_count = buffer.lines_count;
+        self.history = buffer.history;
+        self.history_index = buffer.history_index;
+        self.unsaved_stated = buffer.unsaved_stated;
+        self.saved_history_len = buffer.saved_history_len;
+        self.mapped_content = buffer.mapped_content;
+        self.protected_ranges = buffer.protected_ranges;
+        self.view_edits = buffer.view_edits;
+        self.event_queue = buffer.event_queue;
+        self.git_changes = buffer.git_changes;
+        self.blame = buffer.blame;
+        self.tail = buffer.tail;
+        if self.lines.is_empty() {
+            return;
+        }
+        self.shift_view_state();
+        self.sanitize_view_state();
+    }
+
+    pub(crate) fn record_view_edit(&mut self, start: &Pos<usize>, end: &Pos<usize>, new_end: &Pos<usize>) {
+        if let Some(view_edits) = self.view_edits.as_mut() {
+            view_edits.push(ReplacedRange { start: start.clone(), end: end.clone(), new_end: new_end.clone() });
+        }
+    }
+
+    // Edits made by the other view while it had the buffer: cursor and selection follow their text.
+    fn shift_view_state(&mut self) {
+        let view_edits = match self.view_edits.as_mut() {
+            Some(view_edits) if !view_edits.is_empty() => std::mem::take(view_edits),
+            _ => return,
+        };
+        let shift = |index: &mut Option<Pos<usize>>| if let Some(index) = index.as_mut() {
+            *index = view_edits.iter().fold(index.clone(), |index, edit| shift_position(&index, edit));
+        };
+        shift(&mut self.selection_start_index);
+        shift(&mut self.selection_end_index);
+        shift(&mut self.start_dragged_index);
+        shift(&mut self.stop_dragged_index);
+        // column selection x are display columns, only their line follows the edits
+        let shift_line = |index: &mut Option<Pos<usize>>| if let Some(index) = index.as_mut() {
+            index.y = view_edits.iter().fold(Pos { x: 0, y: index.y }, |index, edit| shift_position(&index, edit)).y;
+        };
+        shift_line(&mut self.column_selection_anchor);
+        shift_line(&mut self.column_selection_head);
+        let cursor_index = view_edits.iter().fold(self.cursor_index.clone(), |index, edit| shift_position(&index, edit));
+        self.set_cursor_y(cursor_index.y.min(self.lines.len() - 1));
+        self.set_cursor_x(cursor_index.x);
+    }
+
+    // Lines have been replaced: cursor and selection are kept within their bounds.
+    pub(crate) fn sanitize_view_state(&mut self) {
+        self.sanitize_cursor_position();
+        let is_out_of_bounds = |index: &Option<Pos<usize>>| index.as_ref().map_or(false, |index| index.y >= self.lines.len());
+        if is_out_of_bounds(&self.selection_start_index) || is_out_of_bounds(&self.selection_end_index)
+            || is_out_of_bounds(&self.column_selection_anchor) || is_out_of_bounds(&self.column_selection_head) {
+            self.reset_selection();
+        } else if self.has_selection() {
+            // selected indexes may be after the end of an edited line
+            self.set_selection();
+        }
+        *self.opening_char_index.borrow_mut() = None;
+        *self.closing_char_index.borrow_mut() = None;
+    }
+}
+
+// Position after text between start and end has been replaced by text ending at new_end.
+// A position within the replaced text stays where it is, unless it is after the new text.
+fn shift_position(index: &Pos<usize>, edit: &ReplacedRange) -> Pos<usize> {
+    if !TextEditor::is_before(&edit.start, index) {
+        return index.clone();
+    }
+    if !TextEditor::is_before(index, &edit.end) {
+        let x = if index.y == edit.end.y { index.x - edit.end.x + edit.new_end.x } else { index.x };
+        return Pos { x, y: index.y - edit.end.y + edit.new_end.y };
+    }
+    if TextEditor::is_before(index, &edit.new_end) { index.clone() } else { edit.new_end.clone() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::text_editor::Pos;
+    use super::{shift_position, ReplacedRange};
+
+    fn shift(index: (usize, usize), start: (usize, usize), end: (usize, usize), new_end: (usize, usize)) -> (usize, usize) {
+        let edit = ReplacedRange { start: Pos { x: start.0, y: start.1 }, end: Pos { x: end.0, y: end.1 }, new_end: Pos { x: new_end.0, y: new_end.1 } };
+        let index = shift_position(&Pos { x: index.0, y: index.1 }, &edit);
+        (index.x, index.y)
+    }
+
+    #[test]
+    fn positions_follow_their_text() {
+        // "ab" typed at the start of line 1
+        assert_eq!(shift((3, 1), (0, 1), (0, 1), (2, 1)), (5, 1));
+        assert_eq!(shift((3, 0), (0, 1), (0, 1), (2, 1)), (3, 0));
+        // line break inserted in line 1, after column 2
+        assert_eq!(shift((4, 1), (2, 1), (2, 1), (0, 2)), (2, 2));
+        assert_eq!(shift((1, 3), (2, 1), (2, 1), (0, 2)), (1, 4));
+        // lines 1 and 2 removed
+        assert_eq!(shift((5, 4), (0, 1), (0, 3), (0, 1)), (5, 2));
+    }
+
+    #[test]
+    fn positions_within_replaced_text_are_kept_within_new_text() {
+        // "hello world" on line 0 replaced by "hi"
+        assert_eq!(shift((1, 0), (0, 0), (11, 0), (2, 0)), (1, 0));
+        assert_eq!(shift((8, 0), (0, 0), (11, 0), (2, 0)), (2, 0));
+        // position at the start of the replaced text stays there
+        assert_eq!(shift((0, 0), (0, 0), (11, 0), (2, 0)), (0, 0));
+    }
+}
@@ -0,0 +1,96 @@
+use eframe::egui::{Pos2, Rect, Rounding, Stroke};
+use eframe::epaint::{RectShape, Shape};
+use crate::text_editor::TextEditor;
+
+const StickyScrollMaxLines: usize = 5;
+// Enclosing scopes are searched in this number of lines above the first visible line.
+const StickyScrollMaxScan: usize = 5000;
+
+// Header lines of the scopes enclosing the first visible line are pinned at the top of the viewport.
+// Scopes are derived from indentation: a header is the closest line above with a smaller indentation.
+pub(crate) trait StickyScroll {
+    fn update_sticky_lines(&mut self, first_line_index: usize);
+    fn sticky_line_at(&self, pointer: Pos2) -> Option<(usize, usize)>;
+    fn sticky_scroll_shapes(&self) -> Vec<Shape>;
+}
+
+impl StickyScroll for TextEditor {
+    fn update_sticky_lines(&mut self, first_line_index: usize) {
+        self.sticky_lines.clear();
+        if !self.options.sticky_scroll {
+            return;
+        }
+        // Pinned headers hide the first lines, so headers are the ones of the first line displayed below them.
+        let mut sticky_lines = vec![];
+        for _ in 0..StickyScrollMaxLines {
+            let line_below_index = first_line_index + sticky_lines.len();
+            if line_below_index >= self.lines.len() {
+                break;
+            }
+            let mut headers = self.enclosing_scope_headers(line_below_index);
+            headers.truncate(StickyScrollMaxLines);
+            let is_stable = headers.len() == sticky_lines.len();
+            sticky_lines = headers;
+            if is_stable {
+                break;
+            }
+        }
+        self.sticky_lines = sticky_lines;
+    }
+
+    // Returns row in the sticky area and line index of the header under the pointer
+    fn sticky_line_at(&self, pointer: Pos2) -> Option<(usize, usize)> {
+        if pointer.y < self.text_editor_viewport.min.y || pointer.x < self.text_editor_viewport.min.x {
+            return None;
+        }
+        let row = ((pointer.y - self.text_editor_viewport.min.y) / self.line_height) as usize;
+        self.sticky_lines.get(row).map(|line_index| (row, *line_index))
+    }
+
+    fn sticky_scroll_shapes(&self) -> Vec<Shape> {
+        if self.sticky_lines.is_empty() {
+            return vec![];
+        }
+        let bottom = self.text_editor_viewport.min.y + self.sticky_lines.len() as f32 * self.line_height;
+        vec![
+            Shape::Rect(RectShape {
+                rect: Rect {
+                    min: self.text_editor_viewport.min,
+                    max: Pos2 { x: self.text_editor_viewport.max.x, y: bottom },
+                },
+                rounding: Rounding::ZERO,
+                fill: self.options.theme.sticky_scroll_background,
+                stroke: Default::default(),
+                fill_texture_id: Default::default(),
+                uv: Rect::ZERO,
+            }),
+            Shape::line_segment([Pos2 { x: self.text_editor_viewport.min.x, y: bottom }, Pos2 { x: self.text_editor_viewport.max.x, y: bottom }],
+                                Stroke::new(1.0, self.options.theme.sticky_scroll_border)),
+        ]
+    }
+}
+
+impl TextEditor {
+    // From outermost to innermost scope
+    fn enclosing_scope_headers(&self, line_index: usize) -> Vec<usize> {
+        let mut headers = vec![];
+        let mut indentation = self.indentation_at(line_index);
+        let mut y = line_index;
+        let lower_bound = line_index.saturating_sub(StickyScrollMaxScan);
+        while y > lower_bound && indentation > 0 {
+            y -= 1;
+            let line = self.lines[y].trim();
+            // closing lines ("}", ")"...) end a scope, they don't open one
+            if line.is_empty() || line.starts_with(['}', ')', ']']) {
+                continue;
+            }
+            let line_indentation = self.indentation_at(y);
+            if line_indentation < indentation {
+                headers.push(y);
+                indentation = line_indentation;
+            }
+        }
+        headers.reverse();
+        headers
+    }
+}
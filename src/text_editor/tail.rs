@@ -0,0 +1,326 @@
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use eframe::egui::Ui;
+use crate::text_editor::{Pos, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+const TailPollInterval: Duration = Duration::from_millis(200);
+
+pub(crate) struct Tail {
+    path: PathBuf,
+    file: File,
+    // Bytes already read
+    offset: u64,
+    // Incomplete utf-8 sequence at the end of the last read
+    pending_bytes: Vec<u8>,
+    last_poll_time: f64,
+    // Read error which stopped following
+    error: Option<io::Error>,
+}
+
+enum TailRead {
+    Appended(String),
+    // File has been truncated or replaced (e.g: log rotation), text is read again from the start
+    Truncated(String),
+}
+
+impl Tail {
+    fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self { path: path.to_path_buf(), file: File::open(path)?, offset: 0, pending_bytes: vec![], last_poll_time: 0.0, error: None })
+    }
+
+    // Text appended since last read
+    fn read(&mut self) -> io::Result<TailRead> {
+        let is_rotated = self.is_rotated();
+        if is_rotated {
+            self.file = File::open(&self.path)?;
+        }
+        let len = self.file.metadata()?.len();
+        let is_truncated = is_rotated || len < self.offset;
+        if is_truncated {
+            self.offset = 0;
+            self.pending_bytes.clear();
+        }
+        let mut text = String::new();
+        if len > self.offset {
+            self.file.seek(SeekFrom::Start(self.offset))?;
+            let mut bytes = std::mem::take(&mut self.pending_bytes);
+            let read_count = (&self.file).take(len - self.offset).read_to_end(&mut bytes)?;
+            self.offset += read_count as u64;
+            text = match std::str::from_utf8(&bytes) {
+                Ok(text) => text.to_string(),
+                // sequence is completed by the next read
+                Err(error) if error.error_len().is_none() => {
+                    self.pending_bytes = bytes[error.valid_up_to()..].to_vec();
+                    String::from_utf8_lossy(&bytes[..error.valid_up_to()]).to_string()
+                }
+                Err(_) => String::from_utf8_lossy(&bytes).to_string(),
+            };
+        }
+        Ok(if is_truncated { TailRead::Truncated(text) } else { TailRead::Appended(text) })
+    }
+
+    // Path leads to another file than the opened one: the followed file has been renamed or removed, and created again.
+    // Until a file is created again at the path, the opened file is still read.
+    fn is_rotated(&self) -> bool {
+        match (fs::metadata(&self.path), self.file.metadata()) {
+            (Ok(path_metadata), Ok(file_metadata)) => !is_same_file(&path_metadata, &file_metadata),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(unix)]
+fn is_same_file(path_metadata: &Metadata, file_metadata: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    path_metadata.dev() == file_metadata.dev() && path_metadata.ino() == file_metadata.ino()
+}
+
+// Without inodes, a file at the path smaller than the opened one is taken for a new file
+#[cfg(not(unix))]
+fn is_same_file(path_metadata: &Metadata, file_metadata: &Metadata) -> bool {
+    path_metadata.len() >= file_metadata.len()
+}
+
+// Follow a growing file (e.g: logs): appended bytes are read incrementally and added to lines, outside of undo history.
+// Content can't be edited while following. Viewport sticks to the bottom, unless it has been scrolled up.
+pub trait TailMode {
+    // Content is replaced by the file content, then appended data is added until stop_following is called.
+    // Fails when content has unsaved changes, as they would be lost.
+    fn follow(&mut self, path: &Path) -> io::Result<()>;
+    fn stop_following(&mut self);
+    fn is_following(&self) -> bool;
+    // Read error which stopped following, until follow or stop_following is called
+    fn follow_error(&self) -> Option<&io::Error>;
+}
+
+impl TailMode for TextEditor {
+    fn follow(&mut self, path: &Path) -> io::Result<()> {
+        if self.is_dirty() {
+            return Err(io::Error::new(io::ErrorKind::Other, "content has unsaved changes"));
+        }
+        let mut tail = Tail::open(path)?;
+        let text = match tail.read()? {
+            TailRead::Appended(text) | TailRead::Truncated(text) => text,
+        };
+        self.tail = Some(tail);
+        self.mapped_content = None;
+        self.clear_lines();
+        self.append_text(&text);
+        Ok(())
+    }
+
+    fn stop_following(&mut self) {
+        self.tail = None;
+    }
+
+    fn is_following(&self) -> bool {
+        self.tail.as_ref().map_or(false, |tail| tail.error.is_none())
+    }
+
+    fn follow_error(&self) -> Option<&io::Error> {
+        self.tail.as_ref().and_then(|tail| tail.error.as_ref())
+    }
+}
+
+impl TextEditor {
+    pub(crate) fn update_tail(&mut self, ui: &Ui) {
+        let time = ui.input(|input| input.time);
+        let tail = match self.tail.as_mut() {
+            Some(tail) if tail.error.is_none() => tail,
+            _ => return,
+        };
+        ui.ctx().request_repaint_after(TailPollInterval);
+        if time - tail.last_poll_time < TailPollInterval.as_secs_f64() {
+            return;
+        }
+        let is_first_poll = tail.last_poll_time == 0.0;
+        tail.last_poll_time = time;
+        let read = match tail.read() {
+            Ok(read) => read,
+            Err(error) => {
+                tail.error = Some(error);
+                return;
+            }
+        };
+        let viewport_height = self.text_editor_viewport.height();
+        let previous_lines_count = self.lines.len();
+        match read {
+            TailRead::Appended(text) => self.append_text(&text),
+            TailRead::Truncated(text) => {
+                self.clear_lines();
+                self.append_text(&text);
+            }
+        }
+        let scroll = FollowScroll { offset_y: self.scroll_offset.y, viewport_height, line_height: self.line_height };
+        if let Some(offset_y) = scroll.offset_after_read(previous_lines_count, self.lines.len(), is_first_poll) {
+            self.scroll_to_offset_y = Some(offset_y);
+        }
+    }
+
+    // Appended lines are not recorded in history
+    fn append_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        let end = self.end_index();
+        let new_end = match text.rsplit_once('\n') {
+            Some((start, last_line)) => Pos { x: last_line.grapheme_count(), y: end.y + bytecount::count(start.as_bytes(), b'\n') + 1 },
+            None => Pos { x: end.x + text.grapheme_count(), y: end.y },
+        };
+        self.on_text_replaced(&end, &end, &new_end, text);
+        append_to_lines(&mut self.lines, text);
+        self.lines_count = self.lines.len();
+    }
+
+    fn clear_lines(&mut self) {
+        let end = self.end_index();
+        self.on_text_replaced(&Pos { x: 0, y: 0 }, &end, &Pos { x: 0, y: 0 }, "");
+        self.lines = vec![String::new()];
+        self.reset_replaced_lines_state();
+    }
+
+    fn end_index(&self) -> Pos<usize> {
+        let y = self.lines.len() - 1;
+        Pos { x: self.lines[y].grapheme_count(), y }
+    }
+
+    // Lines have been replaced outside of history, which refers to previous lines
+    pub(crate) fn reset_replaced_lines_state(&mut self) {
+        self.lines_count = self.lines.len();
+        self.history.clear();
+        self.unsaved_stated = None;
+        self.saved_history_len = Some(0);
+        self.sanitize_view_state();
+    }
+}
+
+// Viewport of a followed file, before appended lines are read
+struct FollowScroll {
+    offset_y: f32,
+    viewport_height: f32,
+    line_height: f32,
+}
+
+impl FollowScroll {
+    // Viewport sticks to the bottom when lines are appended, unless it has been scrolled up. Following starts at the bottom.
+    fn offset_after_read(&self, previous_lines_count: usize, lines_count: usize, is_first_poll: bool) -> Option<f32> {
+        let is_at_bottom = self.offset_y + self.viewport_height >= previous_lines_count as f32 * self.line_height - self.line_height;
+        if (is_at_bottom && lines_count != previous_lines_count) || is_first_poll {
+            Some((lines_count as f32 * self.line_height - self.viewport_height).max(0.0))
+        } else {
+            None
+        }
+    }
+}
+
+// Text is appended to the last line, its line breaks start new lines.
+fn append_to_lines(lines: &mut Vec<String>, text: &str) {
+    let mut appended_lines = text.split('\n');
+    if let (Some(last_line), Some(first_appended_line)) = (lines.last_mut(), appended_lines.next()) {
+        last_line.push_str(first_appended_line);
+    }
+    lines.extend(appended_lines.map(|line| line.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use crate::text_editor::test_support::TemporaryPath;
+    use super::*;
+
+    fn read_text(tail: &mut Tail) -> String {
+        match tail.read().unwrap() {
+            TailRead::Appended(text) => text,
+            TailRead::Truncated(_) => panic!("file has not been truncated"),
+        }
+    }
+
+    #[test]
+    fn read_appended_data() {
+        let file = TemporaryPath::new("tail.log");
+        let path = file.path();
+        write_file(path, b"first line\nsecond");
+        let mut tail = Tail::open(path).unwrap();
+        let mut lines = vec![String::new()];
+        let append = |tail: &mut Tail, lines: &mut Vec<String>| append_to_lines(lines, &read_text(tail));
+
+        append(&mut tail, &mut lines);
+        assert_eq!(lines, vec!["first line", "second"]);
+        // partial line is completed by the next read
+        append_file(path, b" line\nthird ");
+        append(&mut tail, &mut lines);
+        assert_eq!(lines, vec!["first line", "second line", "third "]);
+        // nothing appended
+        assert_eq!(read_text(&mut tail), "");
+        // "é" is split between two writes
+        let e_acute = "é".as_bytes();
+        append_file(path, &e_acute[..1]);
+        append(&mut tail, &mut lines);
+        assert_eq!(lines, vec!["first line", "second line", "third "]);
+        append_file(path, &e_acute[1..]);
+        append_file(path, "\n€".as_bytes());
+        append(&mut tail, &mut lines);
+        assert_eq!(lines, vec!["first line", "second line", "third é", "€"]);
+
+        // truncated file is read again from the start
+        write_file(path, b"rotated\n");
+        let text = match tail.read().unwrap() {
+            TailRead::Truncated(text) => text,
+            TailRead::Appended(_) => panic!("file has been truncated"),
+        };
+        assert_eq!(text, "rotated\n");
+        append_file(path, b"next");
+        assert_eq!(read_text(&mut tail), "next");
+    }
+
+    #[test]
+    fn renamed_file_is_followed_at_its_path() {
+        let file = TemporaryPath::new("rotated.log");
+        let rotated_file = TemporaryPath::new("rotated.log.1");
+        let path = file.path();
+        write_file(path, b"old\n");
+        let mut tail = Tail::open(path).unwrap();
+        assert_eq!(read_text(&mut tail), "old\n");
+        std::fs::rename(path, rotated_file.path()).unwrap();
+        // renamed file is still read until a file is created at the path
+        append_file(rotated_file.path(), b"last old\n");
+        assert_eq!(read_text(&mut tail), "last old\n");
+        // new file is longer than the read offset, it is detected by its inode
+        write_file(path, b"new file, longer than the old one\n");
+        let text = match tail.read().unwrap() {
+            TailRead::Truncated(text) => text,
+            TailRead::Appended(_) => panic!("file has been replaced"),
+        };
+        assert_eq!(text, "new file, longer than the old one\n");
+        append_file(path, b"next");
+        assert_eq!(read_text(&mut tail), "next");
+    }
+
+    #[test]
+    fn viewport_sticks_to_the_bottom_unless_scrolled_up() {
+        // 10 lines of 10 pixels in a 50 pixels viewport, bottom offset is 50
+        let scroll = |offset_y: f32| FollowScroll { offset_y, viewport_height: 50.0, line_height: 10.0 };
+        assert_eq!(scroll(50.0).offset_after_read(10, 12, false), Some(70.0));
+        assert_eq!(scroll(20.0).offset_after_read(10, 12, false), None);
+        // nothing appended
+        assert_eq!(scroll(50.0).offset_after_read(10, 10, false), None);
+        // following starts at the bottom, wherever the viewport was
+        assert_eq!(scroll(0.0).offset_after_read(10, 10, true), Some(50.0));
+        // content shorter than the viewport
+        assert_eq!(scroll(0.0).offset_after_read(2, 3, false), Some(0.0));
+    }
+
+    fn write_file(path: &Path, bytes: &[u8]) {
+        OpenOptions::new().write(true).create(true).truncate(true).open(path).unwrap().write_all(bytes).unwrap();
+    }
+
+    fn append_file(path: &Path, bytes: &[u8]) {
+        OpenOptions::new().append(true).open(path).unwrap().write_all(bytes).unwrap();
+    }
+}
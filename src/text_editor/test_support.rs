@@ -0,0 +1,53 @@
+// Helpers of tests reading and writing files, or running git.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// File or directory in the temporary directory, removed when dropped so a failing test cleans up too.
+pub(crate) struct TemporaryPath(PathBuf);
+
+impl TemporaryPath {
+    // Path is unique to the test process, name tells tests apart (e.g: "tail.log").
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("text-editor-{}-{}", std::process::id(), name));
+        remove(&path);
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TemporaryPath {
+    fn drop(&mut self) {
+        remove(&self.0);
+    }
+}
+
+fn remove(path: &Path) {
+    if path.is_dir() {
+        let _ = fs::remove_dir_all(path);
+    } else {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Empty git repository in a temporary directory
+pub(crate) fn temp_repository(name: &str) -> TemporaryPath {
+    let directory = TemporaryPath::new(name);
+    fs::create_dir_all(directory.path()).unwrap();
+    git(directory.path(), &["init", "-q"]);
+    directory
+}
+
+pub(crate) fn git(directory: &Path, args: &[&str]) {
+    let status = Command::new("git").arg("-C").arg(directory)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com", "-c", "commit.gpgsign=false"])
+        .args(args).output().unwrap().status;
+    assert!(status.success(), "git {:?}", args);
+}
+
+pub(crate) fn lines(text: &str) -> Vec<String> {
+    text.split('\n').map(|line| line.to_string()).collect()
+}
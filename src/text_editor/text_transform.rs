@@ -0,0 +1,237 @@
+use eframe::egui::Ui;
+use crate::text_editor::{Pos, Selection, TextEditor};
+use crate::text_editor::unicode::UnicodeLine;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transformation {
+    Uppercase,
+    Lowercase,
+    TitleCase,
+    SnakeCase,
+    CamelCase,
+    KebabCase,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+}
+
+// Commands working on the selection, or on the word under the cursor when nothing is selected.
+pub trait TextTransform {
+    fn transform_text(&mut self, ui: &Ui, transformation: Transformation);
+    fn toggle_case(&mut self, ui: &Ui);
+}
+
+impl TextTransform for TextEditor {
+    fn transform_text(&mut self, ui: &Ui, transformation: Transformation) {
+        let had_selection = self.has_selection();
+        let (start_index, end_index) = if self.has_selection() {
+            (self.selection_start_index.clone().unwrap(), self.selection_end_index.clone().unwrap())
+        } else {
+            let (start_x, end_x) = self.word_range_at(self.cursor_index.y, self.cursor_index.x);
+            (Pos { x: start_x, y: self.cursor_index.y }, Pos { x: end_x, y: self.cursor_index.y })
+        };
+        self.select_range(start_index.clone(), end_index.clone());
+        let text = self.selected_text();
+        let maybe_new_text = match transformation {
+            Transformation::Uppercase => Some(map_lines(&text, |words| words.to_uppercase())),
+            Transformation::Lowercase => Some(map_lines(&text, |words| words.to_lowercase())),
+            Transformation::TitleCase => Some(map_lines(&text, title_case)),
+            Transformation::SnakeCase => Some(map_lines(&text, |words| split_words(words).join("_").to_lowercase())),
+            Transformation::KebabCase => Some(map_lines(&text, |words| split_words(words).join("-").to_lowercase())),
+            Transformation::CamelCase => Some(map_lines(&text, camel_case)),
+            Transformation::Base64Encode => Some(base64_encode(text.as_bytes())),
+            Transformation::Base64Decode => base64_decode(&text).and_then(|bytes| String::from_utf8(bytes).ok()),
+            Transformation::UrlEncode => Some(url_encode(&text)),
+            Transformation::UrlDecode => url_decode(&text),
+        };
+        if maybe_new_text.is_none() || maybe_new_text.as_ref().unwrap() == &text {
+            // Text can't be decoded or is unchanged, nothing to record.
+            if !had_selection {
+                self.reset_selection();
+            }
+            return;
+        }
+        let new_end_index = match self.replace_text(ui, &start_index, &end_index, maybe_new_text.unwrap().as_str()) {
+            Some(new_end_index) => new_end_index,
+            None => return,
+        };
+        self.set_cursor_y(new_end_index.y);
+        self.set_cursor_x(new_end_index.x);
+        self.select_range(start_index, new_end_index);
+    }
+
+    fn toggle_case(&mut self, ui: &Ui) {
+        let text = if self.has_selection() {
+            self.selected_text()
+        } else {
+            let (start_x, end_x) = self.word_range_at(self.cursor_index.y, self.cursor_index.x);
+            let line = &self.lines[self.cursor_index.y];
+            line[line.byte_index_from_grapheme_index(start_x)..line.byte_index_from_grapheme_index(end_x)].to_string()
+        };
+        if text.chars().any(|c| c.is_lowercase()) {
+            self.transform_text(ui, Transformation::Uppercase);
+        } else {
+            self.transform_text(ui, Transformation::Lowercase);
+        }
+    }
+}
+
+// Apply transformation on each line content, leading and trailing whitespaces are kept.
+fn map_lines<F: Fn(&str) -> String>(text: &str, transformation: F) -> String {
+    text.split('\n').map(|line| {
+        let content = line.trim();
+        if content.is_empty() {
+            return line.to_string();
+        }
+        let start = line.len() - line.trim_start().len();
+        let end = start + content.len();
+        format!("{}{}{}", &line[0..start], transformation(content), &line[end..])
+    }).collect::<Vec<String>>().join("\n")
+}
+
+fn title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut is_word_start = true;
+    for c in text.chars() {
+        if is_word_start {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        is_word_start = c.is_whitespace();
+    }
+    result
+}
+
+fn camel_case(text: &str) -> String {
+    split_words(text).iter().enumerate().map(|(i, word)| {
+        let word = word.to_lowercase();
+        if i == 0 {
+            return word;
+        }
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+            None => word,
+        }
+    }).collect()
+}
+
+// Split identifiers into words: "parseHTTPResponse_code" => ["parse", "HTTP", "Response", "code"]
+fn split_words(text: &str) -> Vec<String> {
+    let chars = text.chars().collect::<Vec<char>>();
+    let mut words = vec![];
+    let mut current = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !current.is_empty() {
+            let previous = chars[i - 1];
+            let is_next_lowercase = chars.get(i + 1).map_or(false, |next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && is_next_lowercase) {
+                words.push(std::mem::take(&mut current));
+            }
+        }
+        current.push(*c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = ((b[0] as u32) << 16) | ((b[1] as u32) << 8) | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[((n >> (18 - 6 * i)) & 0x3F) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut result = Vec::with_capacity(text.len() / 4 * 3);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        if c == b'=' {
+            break;
+        }
+        let value = BASE64_ALPHABET.iter().position(|a| *a == c)? as u32;
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(result)
+}
+
+fn url_encode(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            result.push(b as char);
+        } else {
+            result.push_str(format!("%{:02X}", b).as_str());
+        }
+    }
+    result
+}
+
+fn url_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                // from_str_radix alone would accept a sign (e.g: "%+5")
+                let hex = text.get(i + 1..i + 3).filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+                result.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b'+' => {
+                result.push(b' ');
+                i += 1;
+            }
+            b => {
+                result.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(result).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_decode_rejects_invalid_escapes() {
+        assert_eq!(url_decode("a%20b+c%C3%A9").as_deref(), Some("a b cé"));
+        assert_eq!(url_decode("%+5"), None);
+        assert_eq!(url_decode("%-1"), None);
+        assert_eq!(url_decode("%4"), None);
+        assert_eq!(url_decode("%zz"), None);
+        // not utf-8
+        assert_eq!(url_decode("%FF"), None);
+    }
+}
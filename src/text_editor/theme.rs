@@ -0,0 +1,68 @@
+use eframe::egui::Color32;
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub current_line_background: Color32,
+    pub ruler: Color32,
+    pub whitespace: Color32,
+    pub trailing_whitespace: Color32,
+    pub trailing_whitespace_background: Color32,
+    pub indent_guide: Color32,
+    pub active_indent_guide: Color32,
+    pub minimap_background: Color32,
+    pub minimap_text: Color32,
+    pub minimap_slider: Color32,
+    pub selection_marker: Color32,
+    pub word_occurrence_marker: Color32,
+    pub search_match_marker: Color32,
+    pub error_marker: Color32,
+    pub warning_marker: Color32,
+    pub info_marker: Color32,
+    pub sticky_scroll_background: Color32,
+    pub sticky_scroll_border: Color32,
+    pub diff_added_background: Color32,
+    pub diff_removed_background: Color32,
+    pub diff_changed_background: Color32,
+    pub diff_added_word: Color32,
+    pub diff_removed_word: Color32,
+    pub diff_filler_background: Color32,
+    pub git_added: Color32,
+    pub git_modified: Color32,
+    pub git_deleted: Color32,
+    pub blame_text: Color32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            current_line_background: Color32::from_rgb(242, 242, 250),
+            ruler: Color32::from_rgb(210, 210, 210),
+            whitespace: Color32::from_rgb(180, 180, 180),
+            trailing_whitespace: Color32::from_rgb(230, 120, 120),
+            trailing_whitespace_background: Color32::from_rgb(255, 225, 225),
+            indent_guide: Color32::from_rgb(220, 220, 220),
+            active_indent_guide: Color32::from_rgb(140, 140, 140),
+            minimap_background: Color32::from_rgb(248, 248, 248),
+            minimap_text: Color32::from_rgb(150, 150, 150),
+            minimap_slider: Color32::from_rgba_unmultiplied(100, 100, 100, 40),
+            selection_marker: Color32::LIGHT_BLUE,
+            word_occurrence_marker: Color32::YELLOW,
+            search_match_marker: Color32::from_rgb(240, 160, 40),
+            error_marker: Color32::RED,
+            warning_marker: Color32::from_rgb(230, 180, 0),
+            info_marker: Color32::from_rgb(60, 130, 230),
+            sticky_scroll_background: Color32::from_rgb(245, 245, 245),
+            sticky_scroll_border: Color32::from_rgb(200, 200, 200),
+            diff_added_background: Color32::from_rgb(220, 250, 220),
+            diff_removed_background: Color32::from_rgb(255, 225, 225),
+            diff_changed_background: Color32::from_rgb(225, 235, 255),
+            diff_added_word: Color32::from_rgb(160, 230, 160),
+            diff_removed_word: Color32::from_rgb(245, 170, 170),
+            diff_filler_background: Color32::from_rgb(235, 235, 235),
+            git_added: Color32::from_rgb(80, 180, 80),
+            git_modified: Color32::from_rgb(60, 130, 230),
+            git_deleted: Color32::from_rgb(220, 70, 70),
+            blame_text: Color32::from_rgb(160, 160, 160),
+        }
+    }
+}
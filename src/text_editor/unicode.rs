@@ -0,0 +1,92 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// Cursor and selection x indexes are grapheme cluster indexes in a line: "é" written as "e" + combining accent
+// is a single index. Graphemes are displayed on 1 column, or 2 for east asian wide characters and most emojis.
+// Indexes after the end of a line are allowed (e.g: column selection), each one counts for one column.
+pub(crate) trait UnicodeLine {
+    fn grapheme_count(&self) -> usize;
+    fn grapheme_at(&self, index: usize) -> Option<&str>;
+    fn byte_index_from_grapheme_index(&self, index: usize) -> usize;
+    fn column_from_grapheme_index(&self, index: usize) -> usize;
+    fn grapheme_index_from_column(&self, column: usize) -> usize;
+    fn columns_count(&self) -> usize;
+}
+
+impl UnicodeLine for str {
+    fn grapheme_count(&self) -> usize {
+        if self.is_ascii() {
+            return self.len();
+        }
+        self.graphemes(true).count()
+    }
+
+    fn grapheme_at(&self, index: usize) -> Option<&str> {
+        if self.is_ascii() {
+            return self.get(index..index + 1);
+        }
+        self.graphemes(true).nth(index)
+    }
+
+    fn byte_index_from_grapheme_index(&self, index: usize) -> usize {
+        if self.is_ascii() {
+            return index.min(self.len());
+        }
+        self.grapheme_indices(true).nth(index).map(|(i, _)| i).unwrap_or(self.len())
+    }
+
+    fn column_from_grapheme_index(&self, index: usize) -> usize {
+        if self.is_ascii() {
+            return index;
+        }
+        let mut column = 0;
+        let mut count = 0;
+        for grapheme in self.graphemes(true).take(index) {
+            column += grapheme_width(grapheme);
+            count += 1;
+        }
+        column + (index - count)
+    }
+
+    fn grapheme_index_from_column(&self, column: usize) -> usize {
+        if self.is_ascii() {
+            return column;
+        }
+        let mut current_column = 0;
+        let mut count = 0;
+        for (i, grapheme) in self.graphemes(true).enumerate() {
+            let width = grapheme_width(grapheme);
+            if current_column + width > column {
+                // column is inside a wide grapheme, round to the nearest boundary
+                return if (column - current_column) * 2 < width { i } else { i + 1 };
+            }
+            current_column += width;
+            count += 1;
+        }
+        count + (column - current_column)
+    }
+
+    fn columns_count(&self) -> usize {
+        if self.is_ascii() {
+            return self.len();
+        }
+        self.graphemes(true).map(grapheme_width).sum()
+    }
+}
+
+#[inline]
+pub(crate) fn grapheme_width(grapheme: &str) -> usize {
+    // control characters (e.g: tab) have no width but the font still displays a glyph for them
+    grapheme.width().max(1)
+}
+
+// A grapheme made of a single char, used to compare with brackets, word separators...
+#[inline]
+pub(crate) fn single_char(grapheme: &str) -> Option<char> {
+    let mut chars = grapheme.chars();
+    let c = chars.next();
+    if chars.next().is_some() {
+        return None;
+    }
+    c
+}
@@ -0,0 +1,154 @@
+use eframe::egui::{Pos2, Rect, Rounding, Stroke};
+use eframe::epaint::{RectShape, Shape};
+use unicode_segmentation::UnicodeSegmentation;
+use crate::text_editor::{Pos, TextEditor};
+use crate::text_editor::unicode::{grapheme_width, UnicodeLine};
+
+// Spaces are displayed as "·", tabs as "→" and line ends as "¬", trailing whitespaces are tinted.
+// Indentation guides are vertical lines at each indentation level, the one of the block containing the cursor is highlighted.
+pub(crate) trait WhitespaceRendering {
+    fn paint_whitespaces(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>);
+    fn paint_indent_guides(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>);
+}
+
+impl WhitespaceRendering for TextEditor {
+    fn paint_whitespaces(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>) {
+        for line_index in first_line_index..last_line_index.min(self.lines.len()) {
+            let line = &self.lines[line_index];
+            let top = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(line_index, first_line_index);
+            let center_y = top + self.line_height / 2.0;
+            let trailing_start = line.trim_end().grapheme_count();
+            let mut column = 0;
+            for (i, grapheme) in line.graphemes(true).enumerate() {
+                let width = grapheme_width(grapheme);
+                let x = self.column_to_x(column);
+                column += width;
+                if grapheme != " " && grapheme != "\t" {
+                    continue;
+                }
+                let theme = &self.options.theme;
+                let color = if i >= trailing_start { theme.trailing_whitespace } else { theme.whitespace };
+                if i >= trailing_start {
+                    shapes.push(Shape::Rect(RectShape {
+                        rect: Rect {
+                            min: Pos2 { x, y: top },
+                            max: Pos2 { x: x + width as f32 * self.char_width, y: top + self.line_height },
+                        },
+                        rounding: Rounding::ZERO,
+                        fill: theme.trailing_whitespace_background,
+                        stroke: Default::default(),
+                        fill_texture_id: Default::default(),
+                        uv: Rect::ZERO,
+                    }));
+                }
+                if grapheme == " " {
+                    shapes.push(Shape::circle_filled(Pos2 { x: x + self.char_width / 2.0, y: center_y }, (self.char_width / 10.0).max(1.0), color));
+                } else {
+                    let start = Pos2 { x: x + self.char_width * 0.15, y: center_y };
+                    let end = Pos2 { x: x + self.char_width * 0.85, y: center_y };
+                    let head = self.char_width * 0.25;
+                    let stroke = Stroke::new(1.0, color);
+                    shapes.push(Shape::line_segment([start, end], stroke));
+                    shapes.push(Shape::line_segment([end, Pos2 { x: end.x - head, y: end.y - head }], stroke));
+                    shapes.push(Shape::line_segment([end, Pos2 { x: end.x - head, y: end.y + head }], stroke));
+                }
+            }
+            if line_index + 1 < self.lines.len() {
+                // "¬" after the last character
+                let x = self.index_to_x(&Pos { x: line.grapheme_count(), y: line_index }) + self.char_width * 0.15;
+                let right = x + self.char_width * 0.7;
+                let stroke = Stroke::new(1.0, self.options.theme.whitespace);
+                shapes.push(Shape::line_segment([Pos2 { x, y: center_y }, Pos2 { x: right, y: center_y }], stroke));
+                shapes.push(Shape::line_segment([Pos2 { x: right, y: center_y }, Pos2 { x: right, y: center_y + self.line_height * 0.2 }], stroke));
+            }
+        }
+    }
+
+    fn paint_indent_guides(&self, first_line_index: usize, last_line_index: usize, shapes: &mut Vec<Shape>) {
+        let indent_size = self.options.indent_size.max(1);
+        let last_line_index = last_line_index.min(self.lines.len());
+        // the line after the last visible one tells whether a cursor on the last visible line is on a block header
+        let start = first_line_index;
+        let indentations = self.indentations(start, (last_line_index + 1).min(self.lines.len()));
+        let indentation = |y: usize| indentations[y - start];
+        let (active_column, active_start, active_end) = self.active_indent_guide(indent_size, first_line_index, last_line_index, indentation);
+        for line_index in first_line_index..last_line_index {
+            let top = self.text_editor_viewport.min.y + self.index_to_y_in_virtual_scroll(line_index, first_line_index);
+            for guide_column in (0..indentation(line_index)).step_by(indent_size) {
+                let is_active = active_column == Some(guide_column) && line_index >= active_start && line_index <= active_end;
+                let x = self.column_to_x(guide_column);
+                let color = if is_active { self.options.theme.active_indent_guide } else { self.options.theme.indent_guide };
+                shapes.push(Shape::line_segment([Pos2 { x, y: top }, Pos2 { x, y: top + self.line_height }], Stroke::new(1.0, color)));
+            }
+        }
+    }
+}
+
+impl TextEditor {
+    // Indentation in columns. A blank line takes the indentation of the surrounding lines so guides are not interrupted.
+    pub(crate) fn indentation_at(&self, line_index: usize) -> usize {
+        let line = &self.lines[line_index];
+        if !line.trim().is_empty() {
+            return leading_whitespace_columns(line);
+        }
+        let previous = self.lines[..line_index].iter().rev().find(|line| !line.trim().is_empty()).map_or(0, |line| leading_whitespace_columns(line));
+        let next = self.lines[line_index + 1..].iter().find(|line| !line.trim().is_empty()).map_or(0, |line| leading_whitespace_columns(line));
+        previous.min(next)
+    }
+
+    // Indentation of lines from start to end (exclusive), as indentation_at, surrounding lines are scanned once.
+    fn indentations(&self, start: usize, end: usize) -> Vec<usize> {
+        let non_blank_indentation = |line: &String| if line.trim().is_empty() { None } else { Some(leading_whitespace_columns(line)) };
+        // indentation of the next non blank line, from the end
+        let mut next = self.lines[end..].iter().find_map(non_blank_indentation).unwrap_or(0);
+        let mut next_indentations = vec![0; end - start];
+        for y in (start..end).rev() {
+            next = non_blank_indentation(&self.lines[y]).unwrap_or(next);
+            next_indentations[y - start] = next;
+        }
+        let mut previous = self.lines[..start].iter().rev().find_map(non_blank_indentation).unwrap_or(0);
+        (start..end).map(|y| match non_blank_indentation(&self.lines[y]) {
+            Some(indentation) => {
+                previous = indentation;
+                indentation
+            }
+            None => previous.min(next_indentations[y - start]),
+        }).collect()
+    }
+
+    // Guide column of the block containing the cursor, and lines it spans within the visible lines.
+    // Only a visible cursor highlights a guide, so lines outside the viewport are never scanned.
+    fn active_indent_guide<F: Fn(usize) -> usize>(&self, indent_size: usize, first_line_index: usize, last_line_index: usize, indentation_at: F) -> (Option<usize>, usize, usize) {
+        let cursor_y = self.cursor_index.y.min(self.lines.len() - 1);
+        if cursor_y < first_line_index || cursor_y >= last_line_index {
+            return (None, 0, 0);
+        }
+        let mut indentation = indentation_at(cursor_y);
+        // Cursor on a block header: its block is the next, more indented, lines.
+        if cursor_y + 1 < self.lines.len() && indentation_at(cursor_y + 1) > indentation {
+            indentation = indentation_at(cursor_y + 1);
+        }
+        if indentation < indent_size {
+            return (None, 0, 0);
+        }
+        let guide_column = (indentation - 1) / indent_size * indent_size;
+        let is_in_block = |y: usize| indentation_at(y) > guide_column;
+        let mut start = cursor_y;
+        while start > first_line_index && is_in_block(start - 1) {
+            start -= 1;
+        }
+        let mut end = cursor_y;
+        while end + 1 < last_line_index && is_in_block(end + 1) {
+            end += 1;
+        }
+        if !is_in_block(cursor_y) {
+            // cursor is on the header line, guide starts below it
+            start = cursor_y + 1;
+        }
+        (Some(guide_column), start, end)
+    }
+}
+
+fn leading_whitespace_columns(line: &str) -> usize {
+    line.graphemes(true).take_while(|g| g.chars().all(char::is_whitespace)).map(grapheme_width).sum()
+}
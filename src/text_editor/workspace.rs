@@ -0,0 +1,454 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use eframe::egui;
+use eframe::egui::{Event, Key, Pos2, Rect, Sense, Ui};
+use glow_glyph::GlyphBrush;
+use crate::text_editor::{TextEditor, TextEditorOptions};
+use crate::text_editor::diff_view::{DiffSide, DiffView};
+use crate::text_editor::file_watcher::FileWatcher;
+use crate::text_editor::font::Fonts;
+use crate::text_editor::blame::InlineBlame;
+use crate::text_editor::git_gutter::GitGutter;
+use crate::text_editor::language::Language;
+use crate::text_editor::mapped_document::{MappedDocument, MappedLines};
+use crate::text_editor::tail::TailMode;
+
+// Files from this size are mapped in memory instead of being read into lines
+const MappedFileMinSize: u64 = 64 * 1024 * 1024;
+
+pub struct Document {
+    id: usize,
+    pub title: String,
+    pub path: Option<PathBuf>,
+    editor: TextEditor,
+    watcher: Option<FileWatcher>,
+    // File content when it was last loaded, saved or seen changed, only a different content on disk is an external change
+    disk_text: Option<String>,
+    conflict: Option<FileConflict>,
+}
+
+// File changed on disk while its document had unsaved changes
+struct FileConflict {
+    disk_text: String,
+    // Document (left) compared to the file on disk (right)
+    diff_view: Option<DiffView>,
+}
+
+pub enum WorkspaceEvent {
+    // File changed on disk and its document, without unsaved changes, has been reloaded
+    Reloaded(PathBuf),
+    // File changed on disk and its document has unsaved changes, see EditorWorkspace::resolve_conflict
+    Conflict(PathBuf),
+    // Git version of the file can't be read, document is opened without git markers
+    GitBaseError(PathBuf, io::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConflictResolution {
+    // Replace document content with the file on disk
+    Reload,
+    // Keep document content, saving it overwrites the file
+    Keep,
+    // Compare document with the file on disk, accepted hunks are applied to the document
+    Diff,
+}
+
+impl Document {
+    pub fn editor(&self) -> &TextEditor {
+        &self.editor
+    }
+
+    pub fn editor_mut(&mut self) -> &mut TextEditor {
+        &mut self.editor
+    }
+
+    pub fn has_conflict(&self) -> bool {
+        self.conflict.is_some()
+    }
+}
+
+enum TabAction {
+    Activate(usize),
+    Close(usize),
+    Move(usize, usize),
+}
+
+// Many open documents displayed in tabs, each one with its own buffer, cursor, selection, history and scroll.
+// Only the active document is rendered, so all documents share the same glyph brushes.
+pub struct EditorWorkspace {
+    ctx: egui::Context,
+    gl: Arc<glow::Context>,
+    fonts: Fonts,
+    glyph_brush_text_editor: Arc<Mutex<GlyphBrush>>,
+    glyph_brush_line_number: Arc<Mutex<GlyphBrush>>,
+    zoom: f32,
+    options: TextEditorOptions,
+    documents: Vec<Document>,
+    active_index: usize,
+    next_document_id: usize,
+    events: Vec<WorkspaceEvent>,
+}
+
+impl EditorWorkspace {
+    pub fn new(creation_context: &eframe::CreationContext<'_>, options: TextEditorOptions) -> Self {
+        let gl = creation_context.gl.as_ref().unwrap().clone();
+        let fonts = Fonts::load(&options.font);
+        let (glyph_brush_text_editor, glyph_brush_line_number) = TextEditor::build_glyph_brushes(gl.as_ref(), &fonts);
+        Self {
+            ctx: creation_context.egui_ctx.clone(),
+            gl,
+            fonts,
+            glyph_brush_text_editor,
+            glyph_brush_line_number,
+            zoom: 1.0,
+            options,
+            documents: vec![],
+            active_index: 0,
+            next_document_id: 0,
+            events: vec![],
+        }
+    }
+
+    // Returns index of the new document, which becomes the active one.
+    pub fn open(&mut self, title: String, path: Option<PathBuf>, text: String) -> usize {
+        let mut options = self.options.clone();
+        if let Some(language) = path.as_ref().and_then(|path| Language::from_path(path)) {
+            options.language = language;
+        }
+        let mut editor = TextEditor::with_shared_resources(self.gl.clone(), self.fonts.clone(),
+                                                           (self.glyph_brush_text_editor.clone(), self.glyph_brush_line_number.clone()), text, options);
+        if editor.zoom != self.zoom {
+            editor.zoom = self.zoom;
+            editor.update_font_metrics();
+        }
+        self.documents.push(Document { id: self.next_document_id, title, path, editor, watcher: None, disk_text: None, conflict: None });
+        self.next_document_id += 1;
+        self.active_index = self.documents.len() - 1;
+        self.active_index
+    }
+
+    pub fn open_file(&mut self, path: &Path) -> io::Result<usize> {
+        if let Some(index) = self.documents.iter().position(|document| document.path.as_deref() == Some(path)) {
+            self.active_index = index;
+            return Ok(index);
+        }
+        let title = path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string());
+        if fs::metadata(path)?.len() >= MappedFileMinSize {
+            // Huge files are neither watched, nor compared with git
+            let document = MappedDocument::open(path)?;
+            let index = self.open(title, Some(path.to_path_buf()), String::new());
+            self.documents[index].editor.open_mapped(document);
+            return Ok(index);
+        }
+        let text = fs::read_to_string(path)?;
+        let index = self.open(title, Some(path.to_path_buf()), text.clone());
+        self.documents[index].disk_text = Some(text);
+        self.documents[index].watcher = Some(FileWatcher::new(path, self.ctx.clone()));
+        if let Some(base) = self.options.git_base {
+            // Markers are optional, file is opened even when git is not available
+            if let Err(error) = self.documents[index].editor.load_git_base(path, base) {
+                self.events.push(WorkspaceEvent::GitBaseError(path.to_path_buf(), error));
+            }
+        }
+        if self.options.inline_blame {
+            self.documents[index].editor.load_blame(path);
+        }
+        Ok(index)
+    }
+
+    pub fn save_active(&mut self) -> io::Result<()> {
+        if let Some(document) = self.documents.get_mut(self.active_index) {
+            if let Some(path) = document.path.as_ref() {
+                match document.editor.mapped_document() {
+                    Some(mapped_document) => mapped_document.save(path)?,
+                    None => {
+                        let text = document.editor.text();
+                        fs::write(path, &text)?;
+                        document.disk_text = Some(text);
+                    }
+                }
+                document.editor.mark_saved();
+                document.conflict = None;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn close(&mut self, index: usize) {
+        if index >= self.documents.len() {
+            return;
+        }
+        self.documents.remove(index);
+        if index < self.active_index || self.active_index >= self.documents.len() {
+            self.active_index = self.active_index.saturating_sub(1);
+        }
+    }
+
+    pub fn move_document(&mut self, from: usize, to: usize) {
+        if from >= self.documents.len() || to >= self.documents.len() || from == to {
+            return;
+        }
+        let active_id = self.documents.get(self.active_index).map(|document| document.id);
+        let document = self.documents.remove(from);
+        self.documents.insert(to, document);
+        self.active_index = self.documents.iter().position(|document| Some(document.id) == active_id).unwrap_or(0);
+    }
+
+    pub fn activate(&mut self, index: usize) {
+        if index < self.documents.len() {
+            self.active_index = index;
+        }
+    }
+
+    pub fn active_index(&self) -> Option<usize> {
+        if self.documents.is_empty() { None } else { Some(self.active_index) }
+    }
+
+    pub fn active_editor_mut(&mut self) -> Option<&mut TextEditor> {
+        self.documents.get_mut(self.active_index).map(|document| &mut document.editor)
+    }
+
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    pub fn documents_mut(&mut self) -> &mut [Document] {
+        &mut self.documents
+    }
+
+    // Events since last call
+    pub fn take_events(&mut self) -> Vec<WorkspaceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn resolve_conflict(&mut self, ui: &Ui, index: usize, resolution: ConflictResolution) {
+        let document = match self.documents.get_mut(index) {
+            Some(document) if document.conflict.is_some() => document,
+            _ => return,
+        };
+        match resolution {
+            ConflictResolution::Reload => {
+                let conflict = document.conflict.take().unwrap();
+                document.editor.reload(ui, &conflict.disk_text);
+            }
+            ConflictResolution::Keep => document.conflict = None,
+            ConflictResolution::Diff => {
+                // Both sides are rendered in the same frame, diff view has its own glyph brushes.
+                let glyph_brushes = TextEditor::build_glyph_brushes(self.gl.as_ref(), &self.fonts);
+                let mut editor = TextEditor::with_shared_resources(self.gl.clone(), self.fonts.clone(), glyph_brushes, String::new(), self.options.clone());
+                if editor.zoom != self.zoom {
+                    editor.zoom = self.zoom;
+                    editor.update_font_metrics();
+                }
+                let conflict = document.conflict.as_mut().unwrap();
+                conflict.diff_view = Some(DiffView::from_editor(editor, document.editor.text(), conflict.disk_text.clone()));
+            }
+        }
+    }
+
+    pub fn ui(&mut self, ctx: &egui::Context, ui: &mut Ui) {
+        self.handle_shortcuts(ui);
+        self.check_external_changes(ui);
+        let mut top = self.tab_strip(ui);
+        if self.documents.get(self.active_index).map_or(false, |document| document.conflict.is_some()) {
+            top = self.conflict_bar(ui);
+        }
+        if let Some(document) = self.documents.get_mut(self.active_index) {
+            let editor_rect = Rect { min: Pos2 { x: ui.max_rect().min.x, y: top }, max: ui.max_rect().max };
+            // Scroll area state is kept by id, each document needs its own.
+            match document.conflict.as_mut().and_then(|conflict| conflict.diff_view.as_mut()) {
+                Some(diff_view) => ui.allocate_ui_at_rect(editor_rect, |ui| ui.push_id((document.id, "conflict"), |ui| diff_view.ui(ctx, ui))),
+                None => ui.allocate_ui_at_rect(editor_rect, |ui| ui.push_id(document.id, |ui| document.editor.ui(ctx, ui))),
+            };
+        }
+        self.share_font_state();
+    }
+
+    // Clean documents are reloaded when their file changed on disk, the others are in conflict.
+    fn check_external_changes(&mut self, ui: &Ui) {
+        for document in self.documents.iter_mut() {
+            if !document.watcher.as_ref().map_or(false, |watcher| watcher.has_changed()) {
+                continue;
+            }
+            // Appended data of followed files is read by the editor
+            if document.editor.is_following() {
+                continue;
+            }
+            let path = match document.path.clone() {
+                Some(path) => path,
+                None => continue,
+            };
+            // File may have been deleted, or be in the middle of a write
+            let disk_text = match fs::read_to_string(&path) {
+                Ok(disk_text) => disk_text,
+                Err(_) => continue,
+            };
+            // e.g: file has been saved from this workspace, while the document has been edited since
+            if document.disk_text.as_deref() == Some(disk_text.as_str()) {
+                continue;
+            }
+            document.disk_text = Some(disk_text.clone());
+            if disk_text == document.editor.text() {
+                continue;
+            }
+            if document.editor.is_dirty() {
+                document.conflict = Some(FileConflict { disk_text, diff_view: None });
+                self.events.push(WorkspaceEvent::Conflict(path));
+            } else {
+                document.editor.reload(ui, &disk_text);
+                document.conflict = None;
+                self.events.push(WorkspaceEvent::Reloaded(path));
+            }
+        }
+    }
+
+    // Returns y position of the conflict bar bottom
+    fn conflict_bar(&mut self, ui: &mut Ui) -> f32 {
+        let mut maybe_resolution = None;
+        let mut is_diff_applied = false;
+        let mut is_diff_closed = false;
+        let document = &self.documents[self.active_index];
+        let is_comparing = document.conflict.as_ref().map_or(false, |conflict| conflict.diff_view.is_some());
+        let response = ui.horizontal(|ui| {
+            if is_comparing {
+                ui.label("Left: unsaved changes, right: file on disk.");
+                is_diff_applied = ui.button("Apply left").clicked();
+                is_diff_closed = ui.button("Close").clicked();
+            } else {
+                ui.label(format!("{} changed on disk.", document.title));
+                for (label, resolution) in [("Reload", ConflictResolution::Reload), ("Keep", ConflictResolution::Keep), ("Compare", ConflictResolution::Diff)] {
+                    if ui.button(label).clicked() {
+                        maybe_resolution = Some(resolution);
+                    }
+                }
+            }
+        }).response;
+        if let Some(resolution) = maybe_resolution {
+            self.resolve_conflict(ui, self.active_index, resolution);
+        }
+        let document = &mut self.documents[self.active_index];
+        if is_diff_applied {
+            // Document takes left side, with hunks accepted from disk, as a single history entry
+            if let Some(diff_view) = document.conflict.take().and_then(|conflict| conflict.diff_view) {
+                document.editor.replace_content(ui, &diff_view.text(DiffSide::Left));
+            }
+        } else if is_diff_closed {
+            if let Some(conflict) = document.conflict.as_mut() {
+                conflict.diff_view = None;
+            }
+        }
+        response.rect.max.y + ui.spacing().item_spacing.y
+    }
+
+    fn handle_shortcuts(&mut self, ui: &Ui) {
+        if self.documents.len() < 2 {
+            return;
+        }
+        // ctrl+tab activates next tab, ctrl+shift+tab the previous one
+        let maybe_backward = ui.input(|input| input.events.iter().find_map(|event| match event {
+            Event::Key { key: Key::Tab, pressed: true, modifiers, .. } if modifiers.ctrl => Some(modifiers.shift),
+            _ => None
+        }));
+        if let Some(backward) = maybe_backward {
+            let len = self.documents.len();
+            self.active_index = if backward { (self.active_index + len - 1) % len } else { (self.active_index + 1) % len };
+        }
+    }
+
+    // Returns y position of the tab strip bottom
+    fn tab_strip(&mut self, ui: &mut Ui) -> f32 {
+        let mut maybe_action = None;
+        let response = ui.horizontal(|ui| {
+            let mut tab_rects = Vec::with_capacity(self.documents.len());
+            let mut maybe_dragged = None;
+            for (i, document) in self.documents.iter().enumerate() {
+                // ids keep the drag state of a tab attached to it when tabs are moved or closed
+                ui.push_id(document.id, |ui| {
+                    let title = if document.editor.is_dirty() { format!("● {}", document.title) } else { document.title.clone() };
+                    let tab = ui.selectable_label(i == self.active_index, title).interact(Sense::click_and_drag());
+                    if tab.clicked() || tab.drag_started() {
+                        maybe_action = Some(TabAction::Activate(i));
+                    }
+                    if tab.middle_clicked() || ui.small_button("×").clicked() {
+                        maybe_action = Some(TabAction::Close(i));
+                    }
+                    if tab.dragged() {
+                        maybe_dragged = Some(i);
+                    }
+                    tab_rects.push(tab.rect);
+                });
+            }
+            if let (Some(from), Some(pointer)) = (maybe_dragged, ui.input(|input| input.pointer.interact_pos())) {
+                let tab_spans = tab_rects.iter().map(|rect| (rect.min.x, rect.max.x)).collect::<Vec<(f32, f32)>>();
+                if let Some(to) = tab_drop_index(&tab_spans, from, pointer.x) {
+                    maybe_action = Some(TabAction::Move(from, to));
+                }
+            }
+        }).response;
+        match maybe_action {
+            Some(TabAction::Activate(index)) => self.activate(index),
+            Some(TabAction::Close(index)) => self.close(index),
+            Some(TabAction::Move(from, to)) => self.move_document(from, to),
+            None => {}
+        }
+        response.rect.max.y + ui.spacing().item_spacing.y
+    }
+
+    // Active document may have changed fonts or zoom (e.g: ctrl+=), new glyph brushes are shared with other documents.
+    fn share_font_state(&mut self) {
+        if let Some(active) = self.documents.get(self.active_index) {
+            if Arc::ptr_eq(&active.editor.glyph_brush_text_editor, &self.glyph_brush_text_editor) {
+                return;
+            }
+            self.glyph_brush_text_editor = active.editor.glyph_brush_text_editor.clone();
+            self.glyph_brush_line_number = active.editor.glyph_brush_line_number.clone();
+            self.fonts = active.editor.fonts.clone();
+            self.zoom = active.editor.zoom;
+            self.options.font = active.editor.options.font.clone();
+        }
+        for document in self.documents.iter_mut() {
+            if Arc::ptr_eq(&document.editor.glyph_brush_text_editor, &self.glyph_brush_text_editor) {
+                continue;
+            }
+            document.editor.glyph_brush_text_editor = self.glyph_brush_text_editor.clone();
+            document.editor.glyph_brush_line_number = self.glyph_brush_line_number.clone();
+            document.editor.fonts = self.fonts.clone();
+            document.editor.zoom = self.zoom;
+            document.editor.options.font = self.options.font.clone();
+            document.editor.update_font_metrics();
+        }
+    }
+}
+
+// A dragged tab takes the place of another tab once the pointer crosses that tab's midpoint,
+// so the tabs don't swap back and forth while the pointer is over the moved tab.
+fn tab_drop_index(tab_spans: &[(f32, f32)], from: usize, pointer_x: f32) -> Option<usize> {
+    let midpoint = |(min_x, max_x): (f32, f32)| (min_x + max_x) / 2.0;
+    let after = tab_spans[from + 1..].iter().take_while(|span| pointer_x > midpoint(**span)).count();
+    let before = tab_spans[..from].iter().rev().take_while(|span| pointer_x < midpoint(**span)).count();
+    if after > 0 {
+        Some(from + after)
+    } else if before > 0 {
+        Some(from - before)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tab_drop_index;
+
+    #[test]
+    fn tab_moves_once_the_pointer_crosses_a_midpoint() {
+        let tab_spans = [(0.0, 10.0), (10.0, 30.0), (30.0, 40.0)];
+        assert_eq!(tab_drop_index(&tab_spans, 0, 15.0), None);
+        assert_eq!(tab_drop_index(&tab_spans, 0, 25.0), Some(1));
+        assert_eq!(tab_drop_index(&tab_spans, 0, 38.0), Some(2));
+        assert_eq!(tab_drop_index(&tab_spans, 2, 22.0), None);
+        assert_eq!(tab_drop_index(&tab_spans, 2, 18.0), Some(1));
+        assert_eq!(tab_drop_index(&tab_spans, 1, 4.0), Some(0));
+    }
+}